@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+use std::net::UdpSocket;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use bevy::app::ScheduleRunnerSettings;
+use bevy::prelude::*;
+use bevy_renet::renet::{ClientAuthentication, RenetClient, RenetConnectionConfig};
+
+use self::script::{BotScript, BotStep, DEFAULT_SCRIPT_PATH};
+use crate::common::collision::move_and_slide;
+use crate::common::message::{
+    ClientReliable, ClientUnreliable, NetworkEvent, NetworkSpawnCommand, RenetClientExt,
+    ServerBlocking, ServerReliable, ServerUnreliable, PROTOCOL_ID,
+};
+use crate::common::panic_on_error;
+use crate::common::physics::{
+    platformer_step, MoveInput, MovementMode, PlatformerBody, FIXED_DT, FLY_SPEED,
+};
+use crate::common::player::{ConnectInfo, PlayerLocation, PlayerSyncData};
+use crate::common::rules::GameRules;
+use crate::common::tile::{TileRegistry, Tiles, WorldData, MINING_SPEED, TILE_SIZE};
+use crate::common::transfer::{Downloads, Received, TransferKind};
+use crate::common::transport::{
+    run_if_client_connected, ClientTransportPlugin, Compressed, Compression, NetClient,
+};
+
+mod script;
+
+/// Seconds a bot keeps walking in one direction.
+const TURN_SECONDS: f32 = 0.75;
+
+/// Runs `bot [count] [script]`, connecting `count` windowless clients that follow the script.
+pub fn bot() {
+    let mut args = std::env::args()
+        .skip(2)
+        .filter(|arg| !arg.starts_with("--"));
+    let count: usize = match args.next() {
+        Some(count) => count.parse().expect("The bot count must be a number"),
+        None => 1,
+    };
+    let script = BotScript::load(
+        &args
+            .next()
+            .unwrap_or_else(|| DEFAULT_SCRIPT_PATH.to_string()),
+    );
+
+    let bots: Vec<_> = (0..count)
+        .map(|index| {
+            let script = script.clone();
+            thread::Builder::new()
+                .name(format!("bot-{index}"))
+                .spawn(move || run_bot(index, script))
+                .unwrap()
+        })
+        .collect();
+    for bot in bots {
+        if bot.join().is_err() {
+            error!("A bot crashed");
+        }
+    }
+}
+
+/// Where a bot is and what it is doing.
+#[derive(Default)]
+struct BotState {
+    pos: Vec2,
+    body: PlatformerBody,
+    tick: u32,
+    step: usize,
+    step_time: f32,
+    /// Direction the bot is walking in and how long until it picks a new one.
+    direction: Vec2,
+    turn_in: f32,
+}
+
+fn run_bot(index: usize, script: BotScript) {
+    let server_addr = "127.0.0.1:5000".parse().unwrap();
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let current_time = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap();
+    let client_id = current_time.as_millis() as u64 * 1000 + index as u64;
+    let info = ConnectInfo {
+        name: format!("bot-{index}"),
+        spectator: false,
+        compression: Compression::ALL.to_vec(),
+    };
+    let authentication = ClientAuthentication::Unsecure {
+        protocol_id: PROTOCOL_ID,
+        client_id,
+        server_addr,
+        user_data: Some(info.encode()),
+    };
+    let config = RenetConnectionConfig::default();
+    let client = RenetClient::new(current_time, socket, client_id, config, authentication).unwrap();
+
+    App::new()
+        .insert_resource(ScheduleRunnerSettings::run_loop(Duration::from_secs_f32(
+            FIXED_DT,
+        )))
+        .add_plugins(MinimalPlugins)
+        .add_plugin(ClientTransportPlugin)
+        .insert_resource(NetClient::new(Compressed::new(client, info.compression)))
+        .insert_resource(script)
+        .init_resource::<TileRegistry>()
+        .init_resource::<Tiles>()
+        .init_resource::<GameRules>()
+        .init_resource::<BotState>()
+        .init_resource::<Downloads>()
+        .add_system(receive_message_system.with_run_criteria(run_if_client_connected))
+        .add_system(
+            run_script
+                .with_run_criteria(run_if_client_connected)
+                .after(receive_message_system),
+        )
+        .add_system(panic_on_error)
+        .run();
+}
+
+fn grid_pos(pos: Vec2) -> IVec2 {
+    (pos / TILE_SIZE).round().as_ivec2()
+}
+
+/// Moves the bot one frame, wandering in a random direction that changes every now and then
+/// while `walking`. Platformer bots keep simulating while standing so they still fall.
+fn move_bot(
+    state: &mut BotState,
+    client: &mut NetClient,
+    tiles: &Tiles,
+    registry: &TileRegistry,
+    rules: &GameRules,
+    walking: bool,
+    dt: f32,
+) {
+    state.turn_in -= dt;
+    if state.turn_in <= 0.0 {
+        state.turn_in = TURN_SECONDS;
+        let angle = rand::random::<f32>() * std::f32::consts::TAU;
+        state.direction = Vec2::new(angle.cos(), angle.sin());
+    }
+    let direction = if walking { state.direction } else { Vec2::ZERO };
+    match rules.movement {
+        MovementMode::Flying => {
+            if direction != Vec2::ZERO {
+                state.pos = move_and_slide(tiles, registry, state.pos, direction * FLY_SPEED * dt);
+                client.send(ClientUnreliable::PlayerMovement(PlayerLocation(state.pos)));
+            }
+        }
+        MovementMode::Platformer => {
+            let input = MoveInput {
+                left: direction.x < -0.3,
+                right: direction.x > 0.3,
+                jump: direction.y > 0.7,
+            };
+            state.tick += 1;
+            state.pos = platformer_step(tiles, registry, state.pos, &mut state.body, input);
+            client.send(ClientUnreliable::PlatformerInput {
+                tick: state.tick,
+                inputs: vec![input],
+            });
+        }
+    }
+}
+
+fn run_script(
+    mut client: ResMut<NetClient>,
+    mut state: ResMut<BotState>,
+    script: Res<BotScript>,
+    tiles: Res<Tiles>,
+    registry: Res<TileRegistry>,
+    rules: Res<GameRules>,
+    time: Res<Time>,
+) {
+    let step = match script.steps.get(state.step) {
+        Some(step) => step,
+        None => return,
+    };
+    let dt = time.delta_seconds();
+    state.step_time += dt;
+    let walking = matches!(step, BotStep::Walk(_));
+    move_bot(
+        &mut state,
+        &mut client,
+        &tiles,
+        &registry,
+        &rules,
+        walking,
+        dt,
+    );
+    let here = grid_pos(state.pos);
+
+    let done = match step {
+        BotStep::Wait(seconds) | BotStep::Walk(seconds) => state.step_time >= *seconds,
+        BotStep::Place(name, offset) => {
+            match registry.kind(name) {
+                Some(kind) => client.send_event(NetworkEvent::SpawnBlock(here + *offset, kind)),
+                None => warn!(tile = %name, "Bot script places an unknown tile"),
+            }
+            true
+        }
+        BotStep::Mine(offset, seconds) => match tiles.get(&(here + *offset)) {
+            Some(&(id, _)) if state.step_time < *seconds => {
+                client.send(ClientUnreliable::MineBlock(id, dt * MINING_SPEED));
+                false
+            }
+            _ => true,
+        },
+    };
+    if done {
+        state.step = (state.step + 1) % script.steps.len();
+        state.step_time = 0.0;
+    }
+}
+
+fn receive_message_system(
+    mut client: ResMut<NetClient>,
+    mut state: ResMut<BotState>,
+    mut tiles: ResMut<Tiles>,
+    mut registry: ResMut<TileRegistry>,
+    mut rules: ResMut<GameRules>,
+    mut downloads: ResMut<Downloads>,
+) {
+    let own_id = client.client_id();
+    while let Some(message) = client.receive_message(0) {
+        match bincode::deserialize(&message).unwrap() {
+            ServerReliable::Spawn(id, NetworkSpawnCommand::Block(pos, kind)) => {
+                tiles.insert(pos, (id, kind));
+            }
+            ServerReliable::Event(NetworkEvent::BreakBlock(id)) => {
+                tiles.retain(|_, &mut (tile_id, _)| tile_id != id);
+            }
+            ServerReliable::PlayerRespawned(id, PlayerLocation(pos)) if id == own_id => {
+                state.pos = pos;
+                state.body = default();
+            }
+            _ => {}
+        }
+    }
+    while let Some(message) = client.receive_message(1) {
+        match bincode::deserialize(&message).unwrap() {
+            ServerUnreliable::PositionCorrected(PlayerLocation(pos)) => {
+                state.pos = pos;
+            }
+            // Bots don't replay unacknowledged inputs, so only a state for the latest tick, or
+            // a later one the server stepped without us, is safe to adopt.
+            ServerUnreliable::PlatformerState(tick, pos, body) if tick >= state.tick => {
+                state.tick = tick;
+                state.pos = pos;
+                state.body = body;
+            }
+            ServerUnreliable::TransferChunk(chunk) => match downloads.receive(chunk) {
+                Received::Pending => {}
+                Received::Refused(transfer) => {
+                    client.send(ClientReliable::CancelTransfer(transfer))
+                }
+                Received::Complete(TransferKind::World, data) => {
+                    let world = match WorldData::unpack(&data) {
+                        Some(world) => world,
+                        None => continue,
+                    };
+                    *registry = world.registry;
+                    *tiles = world.tiles;
+                }
+                Received::Complete(TransferKind::Players, data) => {
+                    let players: HashMap<u64, PlayerSyncData> = match bincode::deserialize(&data) {
+                        Ok(players) => players,
+                        Err(_) => continue,
+                    };
+                    if let Some(player) = players.get(&own_id) {
+                        state.pos = player.pos;
+                    }
+                }
+                // Bots draw nothing and keep no saves.
+                Received::Complete(TransferKind::Textures | TransferKind::Save, _) => {}
+            },
+            _ => {}
+        }
+    }
+    while let Some(message) = client.receive_message(2) {
+        match bincode::deserialize(&message).unwrap() {
+            ServerBlocking::SyncRules(new_rules) => *rules = new_rules,
+        }
+    }
+    for (transfer, chunks) in downloads.take_acks() {
+        client.send(ClientUnreliable::TransferAck(transfer, chunks));
+    }
+}
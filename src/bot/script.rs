@@ -0,0 +1,31 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_SCRIPT_PATH: &str = "assets/bots/default.ron";
+
+/// One thing a bot does, grid offsets are relative to the tile the bot stands on.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum BotStep {
+    /// Stand still for this many seconds.
+    Wait(f32),
+    /// Walk in random directions for this many seconds.
+    Walk(f32),
+    /// Place a block of the named tile next to the bot.
+    Place(String, IVec2),
+    /// Mine the block next to the bot for at most this many seconds.
+    Mine(IVec2, f32),
+}
+
+/// What a bot does, repeated forever.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BotScript {
+    pub steps: Vec<BotStep>,
+}
+
+impl BotScript {
+    pub fn load(path: &str) -> Self {
+        let data = std::fs::read_to_string(path)
+            .unwrap_or_else(|e| panic!("Could not read bot script {path}: {e}"));
+        ron::from_str(&data).unwrap_or_else(|e| panic!("Invalid bot script {path}: {e}"))
+    }
+}
@@ -2,23 +2,76 @@ use std::collections::HashMap;
 use std::net::UdpSocket;
 use std::time::SystemTime;
 
+use bevy::log::LogPlugin;
 use bevy::math::Vec3Swizzles;
 use bevy::prelude::*;
-use bevy_renet::renet::{ClientAuthentication, RenetClient, RenetConnectionConfig};
-use bevy_renet::{run_if_client_connected, RenetClientPlugin};
+use bevy_renet::renet::{
+    ClientAuthentication, ConnectToken, RenetClient, RenetConnectionConfig, NETCODE_KEY_BYTES,
+};
 
+use self::camera::{cursor_to_world, CameraPlugin};
+use self::conditioner::ConditionerPlugin;
+use self::crafting::CraftingPlugin;
+use self::downloads::DownloadsPlugin;
+pub(crate) use self::downloads::{DownloadedSave, ServerTextures};
+use self::health::{HealthPlugin, HoveredPlayer};
+use self::hotbar::HotbarPlugin;
+use self::network_stats::NetworkStatsPlugin;
+use self::platformer::{PlatformerCorrection, PlatformerPlugin};
+use self::preview::{Placement, PreviewPlugin};
+use self::spectator::SpectatorPlugin;
+use self::team::TeamPlugin;
+use crate::common::collision::move_and_slide;
+use crate::common::inventory::Inventory;
 use crate::common::message::{
-    ClientUnreliable, NetworkEvent, NetworkId, NetworkIds, NetworkSpawnCommand, RenetClientExt,
-    ServerBlocking, ServerReliable, ServerUnreliable, PROTOCOL_ID,
+    ClientReliable, ClientUnreliable, NetworkEvent, NetworkIds, NetworkSpawnCommand,
+    RenetClientExt, ServerBlocking, ServerReliable, ServerUnreliable, PROTOCOL_ID,
 };
 use crate::common::panic_on_error;
-use crate::common::player::{Player, PlayerLocation};
-use crate::common::tile::{spawn_block, GridPos, TileKind, Tiles, TILE_SIZE};
-use crate::{log, multiplayer_role, MultiplayerRole};
+use crate::common::physics::{MovementMode, FLY_SPEED};
+use crate::common::player::{
+    ConnectInfo, Dead, Health, MovementBatch, Player, PlayerIndex, PlayerLocation, PlayerName,
+    PlayerSyncData, PlayerTeam, MAX_HEALTH,
+};
+use crate::common::rules::GameRules;
+use crate::common::tile::{
+    spawn_block, TileRegistry, Tiles, WorldData, MINING_RESET_SECONDS, MINING_SPEED, TILE_SIZE,
+};
+use crate::common::transfer::{Downloads, Received, TransferKind};
+use crate::common::transport::{
+    run_if_client_connected, ClientTransportPlugin, Compressed, Compression, LinkConditioner,
+    LinkConditions, LinkProxy, Metered, NetClient, NetworkStats,
+};
+use crate::{multiplayer_role, MultiplayerRole};
+
+pub use self::replay::replay;
+
+mod camera;
+mod conditioner;
+mod crafting;
+mod downloads;
+mod health;
+mod hotbar;
+mod network_stats;
+mod platformer;
+mod preview;
+mod replay;
+mod spectator;
+mod team;
 
 #[derive(Default)]
-struct Lobby {
-    players: HashMap<u64, Entity>,
+pub(crate) struct Lobby {
+    pub(crate) players: HashMap<u64, Entity>,
+    /// Which player each index in a [`MovementBatch`] stands for.
+    pub(crate) indices: HashMap<PlayerIndex, u64>,
+}
+
+/// Whether the world has arrived. Tile changes that come before it are held back and applied
+/// after, as the world they change doesn't exist yet.
+#[derive(Default)]
+pub(crate) struct WorldSync {
+    pub(crate) loaded: bool,
+    held: Vec<Vec<u8>>,
 }
 
 pub fn client() {
@@ -29,18 +82,49 @@ pub fn client() {
         .unwrap();
     let config = RenetConnectionConfig::default();
     let client_id = current_time.as_millis() as u64;
-    let authentication = ClientAuthentication::Unsecure {
-        protocol_id: PROTOCOL_ID,
-        client_id,
-        server_addr,
-        user_data: None,
+    let args: Vec<String> = std::env::args().skip(2).collect();
+    let info = ConnectInfo {
+        name: args
+            .iter()
+            .find(|arg| !arg.starts_with("--"))
+            .cloned()
+            .unwrap_or_else(|| match multiplayer_role() {
+                MultiplayerRole::Host => "host".to_string(),
+                _ => "client".to_string(),
+            }),
+        spectator: args.iter().any(|arg| arg == "--spectate"),
+        compression: Compression::from_args(&args),
     };
 
+    // Every packet goes through a proxy, so the connection can be made worse while playing.
+    let conditioner = LinkConditioner::new(LinkConditions::from_args(&args));
+    let proxy = LinkProxy::bind(
+        "127.0.0.1:0".parse().unwrap(),
+        server_addr,
+        conditioner.clone(),
+    )
+    .unwrap();
+    // What unsecure authentication would generate, except that the client connects to the first
+    // address, the proxy, while the server only accepts tokens naming its own.
+    let connect_token = ConnectToken::generate(
+        current_time,
+        PROTOCOL_ID,
+        300,
+        client_id,
+        15,
+        vec![proxy.addr(), server_addr],
+        Some(&info.encode()),
+        &[0; NETCODE_KEY_BYTES],
+    )
+    .unwrap();
+    let authentication = ClientAuthentication::Secure { connect_token };
+    proxy.spawn();
+    let stats = NetworkStats::default();
+
     let client = RenetClient::new(current_time, socket, client_id, config, authentication).unwrap();
     App::new()
         .init_resource::<MousePos>()
         .init_resource::<CurrentGridCoord>()
-        .init_resource::<NetworkIds>()
         .insert_resource(ClearColor(Color::rgb(0.35, 0.1, 0.7)))
         .insert_resource(WindowDescriptor {
             title: if matches!(multiplayer_role(), MultiplayerRole::Client) {
@@ -52,21 +136,68 @@ pub fn client() {
             height: 1440. / 2.4,
             ..Default::default()
         })
-        .add_plugins(DefaultPlugins)
-        .add_plugin(RenetClientPlugin)
-        .insert_resource(client)
-        .insert_resource(Lobby::default())
+        // Logging is set up in `main`, for every role alike.
+        .add_plugins_with(DefaultPlugins, |group| group.disable::<LogPlugin>())
+        .add_plugin(NetworkPlugin)
+        .add_plugin(CameraPlugin)
+        .add_plugin(HotbarPlugin)
+        .add_plugin(CraftingPlugin)
+        .add_plugin(PlatformerPlugin)
+        .add_plugin(PreviewPlugin)
+        .add_plugin(HealthPlugin)
+        .add_plugin(TeamPlugin)
+        .add_plugin(SpectatorPlugin)
+        .add_plugin(ConditionerPlugin)
+        .add_plugin(NetworkStatsPlugin)
+        .add_plugin(DownloadsPlugin)
+        .insert_resource(NetClient::new(Compressed::new(
+            Metered::new(client, stats.clone()),
+            info.compression.clone(),
+        )))
+        .insert_resource(conditioner)
+        .insert_resource(stats)
+        .insert_resource(info)
         .add_startup_system(setup)
         .add_system(move_player)
         .add_system(send_player_pos_to_server.after(move_player))
-        .add_system(receive_message_system.with_run_criteria(run_if_client_connected))
-        .add_system(panic_on_error)
-        .add_system(hit_tile)
+        .add_system(mine_tile.after(update_mouse_pos))
+        // Before tiles broken this frame are despawned, which would leave a new crack nothing
+        // to attach to.
+        .add_system(update_cracks.before(receive_message_system))
         .add_system(update_mouse_pos)
-        .add_system(spawn_tile_on_click)
+        .add_system(spawn_tile_on_click.after(update_mouse_pos))
         .run();
 }
 
+/// Keeps the client's copy of the game in sync with the server. Needs no window or input, so
+/// tests can run it headless.
+pub(crate) struct NetworkPlugin;
+
+impl Plugin for NetworkPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(ClientTransportPlugin)
+            .init_resource::<NetworkIds>()
+            .init_resource::<Inventory>()
+            .init_resource::<Tiles>()
+            .init_resource::<GameRules>()
+            .init_resource::<Lobby>()
+            .init_resource::<Downloads>()
+            .init_resource::<WorldSync>()
+            .init_resource::<TileRegistry>()
+            .init_resource::<ServerTextures>()
+            .add_event::<PlatformerCorrection>()
+            .add_event::<DownloadedSave>()
+            .add_startup_system(spawn_local_player)
+            .add_system(receive_message_system.with_run_criteria(run_if_client_connected))
+            .add_system(
+                send_transfer_acks
+                    .with_run_criteria(run_if_client_connected)
+                    .after(receive_message_system),
+            )
+            .add_system(panic_on_error);
+    }
+}
+
 #[derive(Default, Deref, DerefMut)]
 struct MousePos(Vec2);
 
@@ -75,19 +206,14 @@ struct CurrentGridCoord(IVec2);
 
 fn update_mouse_pos(
     window: Res<Windows>,
-    camera: Query<&Transform, With<Camera>>,
+    camera: Query<(&Camera, &GlobalTransform)>,
     mut mouse_pos: ResMut<MousePos>,
     mut grid_coord: ResMut<CurrentGridCoord>,
 ) {
     let window = window.get_primary().unwrap();
-    let width = window.width();
-    let height = window.height();
-    let camera = camera.single().translation;
+    let (camera, camera_transform) = camera.single();
     if let Some(window_pos) = window.cursor_position() {
-        mouse_pos.0 = Vec2::new(
-            camera.x + window_pos.x - width / 2.,
-            camera.x + window_pos.y - height / 2.,
-        );
+        mouse_pos.0 = cursor_to_world(window, window_pos, camera, camera_transform);
         grid_coord.0 = IVec2::new(
             (mouse_pos.x / TILE_SIZE).round() as i32,
             (mouse_pos.y / TILE_SIZE).round() as i32,
@@ -96,42 +222,96 @@ fn update_mouse_pos(
 }
 
 fn spawn_tile_on_click(
-    mut client: ResMut<RenetClient>,
+    mut client: ResMut<NetClient>,
     input: Res<Input<MouseButton>>,
-    tiles: Query<(&NetworkId, &GridPos), With<TileKind>>,
     grid_coord: Res<CurrentGridCoord>,
+    placement: Res<Placement>,
 ) {
-    if input.just_pressed(MouseButton::Right) {
-        if !tiles.iter().any(|(_, &GridPos(pos))| grid_coord.0 == pos) {
-            client.send_event(NetworkEvent::SpawnBlock(grid_coord.0, TileKind::Stone));
+    if input.just_pressed(MouseButton::Right) && placement.valid {
+        if let Some(kind) = placement.kind {
+            client.send_event(NetworkEvent::SpawnBlock(grid_coord.0, kind));
         }
     }
 }
 
-fn hit_tile(
-    mut client: ResMut<RenetClient>,
+fn mine_tile(
+    mut client: ResMut<NetClient>,
     input: Res<Input<MouseButton>>,
-    tiles: Query<(&NetworkId, &GridPos), With<TileKind>>,
+    tiles: Res<Tiles>,
     grid_coord: Res<CurrentGridCoord>,
+    placement: Res<Placement>,
+    hovered: Res<HoveredPlayer>,
+    time: Res<Time>,
 ) {
-    if input.just_pressed(MouseButton::Left) {
-        for (&id, &GridPos(pos)) in &tiles {
-            if grid_coord.0 == pos {
-                client.send_event(NetworkEvent::BreakBlock(id));
-            }
+    if input.pressed(MouseButton::Left)
+        && placement.in_reach
+        && !placement.protected
+        && hovered.is_none()
+    {
+        if let Some(&(id, _)) = tiles.get(&grid_coord.0) {
+            client.send(ClientUnreliable::MineBlock(
+                id,
+                time.delta_seconds() * MINING_SPEED,
+            ));
         }
     }
 }
 
+/// Mining progress of a tile as last reported by the server.
 #[derive(Component)]
-pub struct Remote;
+struct MiningProgress {
+    progress: f32,
+    updated: f64,
+}
+
+#[derive(Component)]
+struct Crack;
 
-fn setup(
+fn update_cracks(
     mut commands: Commands,
-    mut windows: ResMut<Windows>,
-    client: Res<RenetClient>,
-    mut lobby: ResMut<Lobby>,
+    tiles: Query<(Entity, &MiningProgress, Option<&Children>)>,
+    mut cracks: Query<&mut Sprite, With<Crack>>,
+    time: Res<Time>,
 ) {
+    let now = time.seconds_since_startup();
+    for (tile, mining, children) in &tiles {
+        let progress = if now - mining.updated < MINING_RESET_SECONDS {
+            mining.progress
+        } else {
+            commands.entity(tile).remove::<MiningProgress>();
+            0.0
+        };
+        let color = Color::rgba(0.0, 0.0, 0.0, 0.8 * progress);
+
+        let crack = children
+            .into_iter()
+            .flatten()
+            .find(|&&child| cracks.contains(child));
+        match crack {
+            Some(&crack) => cracks.get_mut(crack).unwrap().color = color,
+            None => {
+                commands.entity(tile).with_children(|commands| {
+                    commands
+                        .spawn_bundle(SpriteBundle {
+                            sprite: Sprite {
+                                color,
+                                custom_size: Some(Vec2::new(TILE_SIZE, TILE_SIZE)),
+                                ..default()
+                            },
+                            transform: Transform::from_xyz(0.0, 0.0, 0.05),
+                            ..default()
+                        })
+                        .insert(Crack);
+                });
+            }
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Remote;
+
+fn setup(mut commands: Commands, mut windows: ResMut<Windows>) {
     let window = windows.get_primary_mut().unwrap();
 
     match multiplayer_role() {
@@ -146,15 +326,28 @@ fn setup(
     }
 
     commands.spawn_bundle(Camera2dBundle::default());
+}
 
-    let player = Player::create(&mut commands, Default::default(), false);
-    lobby.players.insert(client.client_id(), player);
+fn spawn_local_player(
+    mut commands: Commands,
+    client: Res<NetClient>,
+    mut lobby: ResMut<Lobby>,
+    info: Res<ConnectInfo>,
+) {
+    if !info.spectator {
+        let player = Player::create(&mut commands, Default::default(), false);
+        lobby.players.insert(client.client_id(), player);
+    }
 }
 
 fn send_player_pos_to_server(
     mut player: Query<&Transform, (Changed<Transform>, With<Player>, Without<Remote>)>,
-    mut client: ResMut<RenetClient>,
+    mut client: ResMut<NetClient>,
+    rules: Res<GameRules>,
 ) {
+    if rules.movement != MovementMode::Flying {
+        return;
+    }
     if let Ok(tf) = player.get_single_mut() {
         client.send(ClientUnreliable::PlayerMovement(PlayerLocation(
             tf.translation.xy(),
@@ -162,104 +355,284 @@ fn send_player_pos_to_server(
     }
 }
 
+#[allow(clippy::type_complexity)]
 fn move_player(
-    mut player: Query<&mut Transform, (With<Player>, Without<Remote>)>,
+    mut player: Query<&mut Transform, (With<Player>, Without<Remote>, Without<Dead>)>,
     input: Res<Input<KeyCode>>,
     time: Res<Time>,
+    tiles: Res<Tiles>,
+    registry: Res<TileRegistry>,
+    rules: Res<GameRules>,
 ) {
-    let speed = 500.0;
-    let mut tf = player.single_mut();
+    if rules.movement != MovementMode::Flying {
+        return;
+    }
+    let speed = FLY_SPEED;
+    let mut tf = match player.get_single_mut() {
+        Ok(tf) => tf,
+        Err(_) => return,
+    };
+    let mut delta = Vec2::ZERO;
     if input.pressed(KeyCode::W) {
-        tf.translation.y += speed * time.delta_seconds();
+        delta.y += speed * time.delta_seconds();
     }
     if input.pressed(KeyCode::A) {
-        tf.translation.x -= speed * time.delta_seconds();
+        delta.x -= speed * time.delta_seconds();
     }
     if input.pressed(KeyCode::S) {
-        tf.translation.y -= speed * time.delta_seconds();
+        delta.y -= speed * time.delta_seconds();
     }
     if input.pressed(KeyCode::D) {
-        tf.translation.x += speed * time.delta_seconds();
+        delta.x += speed * time.delta_seconds();
+    }
+    if delta != Vec2::ZERO {
+        let pos = move_and_slide(&tiles, &registry, tf.translation.xy(), delta);
+        tf.translation.x = pos.x;
+        tf.translation.y = pos.y;
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn receive_message_system(
     mut commands: Commands,
-    mut client: ResMut<RenetClient>,
+    mut client: ResMut<NetClient>,
     mut lobby: ResMut<Lobby>,
     mut network_ids: ResMut<NetworkIds>,
-    mut player_data: Query<(&mut Transform, &mut Sprite)>,
+    mut player_data: Query<(
+        &mut Transform,
+        &mut Sprite,
+        &mut Visibility,
+        &mut Health,
+        &mut PlayerName,
+        &mut PlayerTeam,
+    )>,
+    mut registry: ResMut<TileRegistry>,
+    asset_server: Res<AssetServer>,
+    time: Res<Time>,
+    mut inventory: ResMut<Inventory>,
+    mut world: ResMut<Tiles>,
+    mut rules: ResMut<GameRules>,
+    mut corrections: EventWriter<PlatformerCorrection>,
+    mut downloads: ResMut<Downloads>,
+    mut sync: ResMut<WorldSync>,
+    mut textures: ResMut<ServerTextures>,
+    mut saves: EventWriter<DownloadedSave>,
 ) {
-    while let Some(message) = client.receive_message(0) {
-        match bincode::deserialize(&message).unwrap() {
+    let held = if sync.loaded {
+        std::mem::take(&mut sync.held)
+    } else {
+        Vec::new()
+    };
+    let received = std::iter::from_fn(|| client.receive_message(0));
+    for message in held.into_iter().chain(received) {
+        let decoded = bincode::deserialize(&message).unwrap();
+        if !sync.loaded
+            && matches!(
+                decoded,
+                ServerReliable::Spawn(..) | ServerReliable::Event(_)
+            )
+        {
+            sync.held.push(message);
+            continue;
+        }
+        match decoded {
             ServerReliable::PlayerJoined(id, data) => {
+                lobby.indices.insert(data.index, id);
                 let new_player = Player::create(&mut commands, data, true);
                 lobby.players.insert(id, new_player);
-                log!("Client {} joined", id)
+                info!(client_id = id, "Client joined")
             }
             ServerReliable::PlayerLeft(id) => {
                 let player = lobby.players.remove(&id).unwrap();
+                lobby.indices.retain(|_, &mut index_id| index_id != id);
                 commands.entity(player).despawn();
-                log!("Client {} left", id)
+                info!(client_id = id, "Client left")
             }
             ServerReliable::Event(event) => {
-                log!("Got event {event:?}");
+                debug!(?event, "Got event");
                 match event {
                     NetworkEvent::SpawnBlock(_, _) => unreachable!("can't happen"),
                     NetworkEvent::BreakBlock(id) => {
-                        commands.entity(network_ids.remove(&id).unwrap()).despawn();
+                        commands
+                            .entity(network_ids.remove(&id).unwrap())
+                            .despawn_recursive();
+                        world.retain(|_, &mut (tile_id, _)| tile_id != id);
                     }
                 }
             }
             ServerReliable::Spawn(id, command) => match command {
                 NetworkSpawnCommand::Block(pos, kind) => {
-                    let tile = spawn_block(&mut commands, id, pos, kind);
+                    let tile = spawn_block(&mut commands, &registry, &asset_server, id, pos, kind);
                     network_ids.insert(id, tile);
+                    world.insert(pos, (id, kind));
                 }
             },
-        }
-    }
-    while let Some(message) = client.receive_message(1) {
-        match bincode::deserialize(&message).unwrap() {
-            ServerUnreliable::PlayerMoved(id, PlayerLocation(pos)) => {
-                if let Some(player) = lobby.players.get(&id).copied() {
-                    if let Ok((mut tf, _)) = player_data.get_mut(player) {
+            ServerReliable::InventoryChanged(new_inventory) => {
+                *inventory = new_inventory;
+            }
+            ServerReliable::PlayerDied(id) => {
+                if let Some(&player) = lobby.players.get(&id) {
+                    if let Ok((_, _, mut visibility, mut health, ..)) = player_data.get_mut(player)
+                    {
+                        visibility.is_visible = false;
+                        health.0 = 0.0;
+                    }
+                    commands.entity(player).insert(Dead);
+                }
+                info!(client_id = id, "Client died")
+            }
+            ServerReliable::PlayerRespawned(id, PlayerLocation(pos)) => {
+                if let Some(&player) = lobby.players.get(&id) {
+                    if let Ok((mut tf, _, mut visibility, mut health, ..)) =
+                        player_data.get_mut(player)
+                    {
                         tf.translation.x = pos.x;
                         tf.translation.y = pos.y;
+                        visibility.is_visible = true;
+                        health.0 = MAX_HEALTH;
+                    }
+                    commands.entity(player).remove::<Dead>();
+                }
+            }
+            ServerReliable::PlayerTeamChanged(id, new_team, color) => {
+                if let Some(&player) = lobby.players.get(&id) {
+                    if let Ok((_, mut sprite, .., mut team)) = player_data.get_mut(player) {
+                        sprite.color = color;
+                        team.0 = Some(new_team);
                     }
                 }
             }
         }
     }
-    while let Some(message) = client.receive_message(2) {
+    while let Some(message) = client.receive_message(1) {
         match bincode::deserialize(&message).unwrap() {
-            ServerBlocking::SyncPlayers(players) => {
-                log!("syncing {} players", players.len());
-                for (&client_id, &sync_data) in &players {
-                    match lobby.players.get(&client_id) {
-                        Some(ent) => {
-                            if let Ok((mut tf, mut sprite)) = player_data.get_mut(*ent) {
-                                tf.translation.x = sync_data.pos.x;
-                                tf.translation.y = sync_data.pos.y;
-                                sprite.color = sync_data.color;
-                            }
+            ServerUnreliable::PlayersMoved(MovementBatch(moves)) => {
+                let own_id = client.client_id();
+                for (index, PlayerLocation(pos)) in moves {
+                    // Our own player is predicted rather than moved by the server.
+                    let id = match lobby.indices.get(&index) {
+                        Some(&id) if id != own_id => id,
+                        _ => continue,
+                    };
+                    if let Some(&player) = lobby.players.get(&id) {
+                        if let Ok((mut tf, ..)) = player_data.get_mut(player) {
+                            tf.translation.x = pos.x;
+                            tf.translation.y = pos.y;
                         }
+                    }
+                }
+            }
+            ServerUnreliable::PositionCorrected(PlayerLocation(pos)) => {
+                if let Some(&player) = lobby.players.get(&client.client_id()) {
+                    if let Ok((mut tf, ..)) = player_data.get_mut(player) {
+                        tf.translation.x = pos.x;
+                        tf.translation.y = pos.y;
+                    }
+                }
+            }
+            ServerUnreliable::PlayerHealth(id, new_health) => {
+                if let Some(&player) = lobby.players.get(&id) {
+                    if let Ok((_, _, _, mut health, ..)) = player_data.get_mut(player) {
+                        health.0 = new_health;
+                    }
+                }
+            }
+            ServerUnreliable::PlatformerState(tick, pos, body) => {
+                corrections.send(PlatformerCorrection { tick, pos, body });
+            }
+            ServerUnreliable::TransferChunk(chunk) => match downloads.receive(chunk) {
+                Received::Pending => {}
+                Received::Refused(transfer) => {
+                    warn!(transfer, "Refused a transfer");
+                    client.send(ClientReliable::CancelTransfer(transfer));
+                }
+                Received::Complete(TransferKind::World, data) => {
+                    let WorldData {
+                        registry: new_registry,
+                        tiles,
+                    } = match WorldData::unpack(&data) {
+                        Some(world) => world,
                         None => {
-                            lobby
-                                .players
-                                .insert(client_id, Player::create(&mut commands, sync_data, true));
+                            warn!("Dropped a malformed world");
+                            continue;
                         }
+                    };
+                    debug!(count = tiles.len(), "Received tiles");
+                    // The world's tiles index into the server's registry, not the one on disk.
+                    *registry = new_registry;
+                    for (&pos, &(id, kind)) in tiles.iter() {
+                        let tile =
+                            spawn_block(&mut commands, &registry, &asset_server, id, pos, kind);
+                        network_ids.insert(id, tile);
                     }
+                    *world = tiles;
+                    sync.loaded = true;
                 }
-            }
-            ServerBlocking::SyncWorld(tiles) => {
-                log!("Received {} tiles", tiles.len());
-                for (&pos, &(id, kind)) in &tiles {
-                    let tile = spawn_block(&mut commands, id, pos, kind);
-                    network_ids.insert(id, tile);
+                Received::Complete(TransferKind::Textures, data) => {
+                    match bincode::deserialize(&data) {
+                        Ok(new_textures) => *textures = ServerTextures(new_textures),
+                        Err(_) => warn!("Dropped malformed tile textures"),
+                    }
+                }
+                Received::Complete(TransferKind::Players, data) => {
+                    let players: HashMap<u64, PlayerSyncData> = match bincode::deserialize(&data) {
+                        Ok(players) => players,
+                        Err(_) => {
+                            warn!("Dropped a malformed player list");
+                            continue;
+                        }
+                    };
+                    debug!(count = players.len(), "Syncing players");
+                    for (client_id, sync_data) in players {
+                        lobby.indices.insert(sync_data.index, client_id);
+                        match lobby.players.get(&client_id) {
+                            Some(ent) => {
+                                if let Ok((mut tf, mut sprite, _, mut health, mut name, mut team)) =
+                                    player_data.get_mut(*ent)
+                                {
+                                    tf.translation.x = sync_data.pos.x;
+                                    tf.translation.y = sync_data.pos.y;
+                                    sprite.color = sync_data.color;
+                                    health.0 = sync_data.health;
+                                    name.0 = sync_data.name;
+                                    team.0 = sync_data.team;
+                                }
+                            }
+                            None => {
+                                lobby.players.insert(
+                                    client_id,
+                                    Player::create(&mut commands, sync_data, true),
+                                );
+                            }
+                        }
+                    }
+                }
+                Received::Complete(TransferKind::Save, data) => {
+                    saves.send(DownloadedSave(data));
+                }
+            },
+            ServerUnreliable::BlockDamaged(id, progress) => {
+                if let Some(&tile) = network_ids.get(&id) {
+                    commands.entity(tile).insert(MiningProgress {
+                        progress,
+                        updated: time.seconds_since_startup(),
+                    });
                 }
-                commands.insert_resource(Tiles(tiles));
             }
         }
     }
+    while let Some(message) = client.receive_message(2) {
+        match bincode::deserialize(&message).unwrap() {
+            ServerBlocking::SyncRules(new_rules) => {
+                info!(movement = ?new_rules.movement, "Got the game rules");
+                *rules = new_rules;
+            }
+        }
+    }
+}
+
+fn send_transfer_acks(mut client: ResMut<NetClient>, mut downloads: ResMut<Downloads>) {
+    for (transfer, chunks) in downloads.take_acks() {
+        client.send(ClientUnreliable::TransferAck(transfer, chunks));
+    }
 }
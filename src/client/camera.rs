@@ -0,0 +1,98 @@
+use bevy::input::mouse::MouseWheel;
+use bevy::math::Vec3Swizzles;
+use bevy::prelude::*;
+
+use super::Remote;
+use crate::common::player::Player;
+
+/// How the camera tracks the local player.
+pub struct CameraSettings {
+    /// Half the size of the area around the screen centre the player can move in freely.
+    pub deadzone: Vec2,
+    /// How quickly the camera catches up with the player, higher is snappier.
+    pub smoothing: f32,
+    pub min_zoom: f32,
+    pub max_zoom: f32,
+    /// Zoom factor applied per scroll wheel line.
+    pub zoom_step: f32,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self {
+            deadzone: Vec2::new(150.0, 100.0),
+            smoothing: 8.0,
+            min_zoom: 0.5,
+            max_zoom: 3.0,
+            zoom_step: 1.1,
+        }
+    }
+}
+
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CameraSettings>()
+            .add_system(zoom_camera)
+            .add_system(follow_player);
+    }
+}
+
+/// Whether scroll wheel input is meant for zooming rather than the hotbar.
+pub fn zoom_modifier_held(input: &Input<KeyCode>) -> bool {
+    input.any_pressed([KeyCode::LControl, KeyCode::RControl])
+}
+
+fn follow_player(
+    player: Query<&Transform, (With<Player>, Without<Remote>)>,
+    mut camera: Query<(&mut Transform, &OrthographicProjection), Without<Player>>,
+    settings: Res<CameraSettings>,
+    time: Res<Time>,
+) {
+    let target = match player.get_single() {
+        Ok(tf) => tf.translation.xy(),
+        Err(_) => return,
+    };
+    let (mut camera, projection) = camera.single_mut();
+    let current = camera.translation.xy();
+
+    let deadzone = settings.deadzone * projection.scale;
+    let offset = target - current;
+    let desired = current + offset - offset.clamp(-deadzone, deadzone);
+
+    let t = 1.0 - (-settings.smoothing * time.delta_seconds()).exp();
+    let pos = current.lerp(desired, t);
+    camera.translation.x = pos.x;
+    camera.translation.y = pos.y;
+}
+
+fn zoom_camera(
+    mut scroll: EventReader<MouseWheel>,
+    mut camera: Query<&mut OrthographicProjection, With<Camera>>,
+    input: Res<Input<KeyCode>>,
+    settings: Res<CameraSettings>,
+) {
+    if !zoom_modifier_held(&input) {
+        scroll.clear();
+        return;
+    }
+    let mut projection = camera.single_mut();
+    for event in scroll.iter() {
+        projection.scale = (projection.scale * settings.zoom_step.powf(-event.y))
+            .clamp(settings.min_zoom, settings.max_zoom);
+    }
+}
+
+/// Converts a cursor position in window coordinates to world coordinates.
+pub fn cursor_to_world(
+    window: &Window,
+    cursor: Vec2,
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+) -> Vec2 {
+    let window_size = Vec2::new(window.width(), window.height());
+    let ndc = cursor / window_size * 2.0 - Vec2::ONE;
+    let ndc_to_world = camera_transform.compute_matrix() * camera.projection_matrix().inverse();
+    ndc_to_world.project_point3(ndc.extend(-1.0)).xy()
+}
@@ -0,0 +1,192 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::common::transport::{LinkConditioner, LinkConditions};
+
+const BUTTON_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 0.6);
+const HOVERED_BUTTON_COLOR: Color = Color::rgba(0.2, 0.2, 0.2, 0.8);
+const LATENCY_STEP: Duration = Duration::from_millis(10);
+const CHANCE_STEP: f32 = 0.01;
+
+/// One setting of the simulated connection.
+#[derive(Clone, Copy)]
+enum Knob {
+    Latency,
+    Jitter,
+    Loss,
+    Duplicate,
+    Reorder,
+}
+
+impl Knob {
+    const ALL: [Knob; 5] = [
+        Knob::Latency,
+        Knob::Jitter,
+        Knob::Loss,
+        Knob::Duplicate,
+        Knob::Reorder,
+    ];
+
+    fn turn(self, conditions: &mut LinkConditions, steps: i32) {
+        let duration = |value: Duration| {
+            if steps < 0 {
+                value.saturating_sub(LATENCY_STEP)
+            } else {
+                value + LATENCY_STEP
+            }
+        };
+        let chance = |value: f32| value + CHANCE_STEP * steps as f32;
+        match self {
+            Knob::Latency => conditions.latency = duration(conditions.latency),
+            Knob::Jitter => conditions.jitter = duration(conditions.jitter),
+            Knob::Loss => conditions.loss = chance(conditions.loss),
+            Knob::Duplicate => conditions.duplicate = chance(conditions.duplicate),
+            Knob::Reorder => conditions.reorder = chance(conditions.reorder),
+        }
+    }
+
+    fn describe(self, conditions: &LinkConditions) -> String {
+        let percent = |value: f32| format!("{:>4.0} %", value * 100.0);
+        match self {
+            Knob::Latency => format!("latency   {:>4} ms", conditions.latency.as_millis()),
+            Knob::Jitter => format!("jitter    {:>4} ms", conditions.jitter.as_millis()),
+            Knob::Loss => format!("loss      {}", percent(conditions.loss)),
+            Knob::Duplicate => format!("duplicate {}", percent(conditions.duplicate)),
+            Knob::Reorder => format!("reorder   {}", percent(conditions.reorder)),
+        }
+    }
+}
+
+#[derive(Component)]
+struct ConditionerPanel;
+
+#[derive(Component)]
+struct KnobLabel(Knob);
+
+#[derive(Component)]
+struct KnobButton(Knob, i32);
+
+/// A debug panel, toggled with F2, to make the connection worse while playing.
+pub struct ConditionerPlugin;
+
+impl Plugin for ConditionerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(setup_conditioner_panel)
+            .add_system(toggle_conditioner_panel)
+            .add_system(turn_knob_on_click)
+            .add_system(update_knob_labels.after(turn_knob_on_click));
+    }
+}
+
+fn setup_conditioner_panel(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let style = TextStyle {
+        font: asset_server.load("fonts/DejaVuSansMono.ttf"),
+        font_size: 16.0,
+        color: Color::WHITE,
+    };
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(10.0),
+                    bottom: Val::Px(80.0),
+                    ..default()
+                },
+                flex_direction: FlexDirection::ColumnReverse,
+                padding: UiRect::all(Val::Px(6.0)),
+                ..default()
+            },
+            color: BUTTON_COLOR.into(),
+            visibility: Visibility { is_visible: false },
+            ..default()
+        })
+        .insert(ConditionerPanel)
+        .with_children(|commands| {
+            commands.spawn_bundle(TextBundle::from_section(
+                "Simulated connection",
+                style.clone(),
+            ));
+            for knob in Knob::ALL {
+                commands
+                    .spawn_bundle(NodeBundle {
+                        style: Style {
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        color: Color::NONE.into(),
+                        ..default()
+                    })
+                    .with_children(|commands| {
+                        commands
+                            .spawn_bundle(TextBundle::from_section("", style.clone()))
+                            .insert(KnobLabel(knob));
+                        for (label, steps) in [("-", -1), ("+", 1)] {
+                            commands
+                                .spawn_bundle(ButtonBundle {
+                                    style: Style {
+                                        margin: UiRect::all(Val::Px(2.0)),
+                                        padding: UiRect::new(
+                                            Val::Px(8.0),
+                                            Val::Px(8.0),
+                                            Val::Px(2.0),
+                                            Val::Px(2.0),
+                                        ),
+                                        ..default()
+                                    },
+                                    color: BUTTON_COLOR.into(),
+                                    ..default()
+                                })
+                                .insert(KnobButton(knob, steps))
+                                .with_children(|commands| {
+                                    commands.spawn_bundle(TextBundle::from_section(
+                                        label,
+                                        style.clone(),
+                                    ));
+                                });
+                        }
+                    });
+            }
+        });
+}
+
+fn toggle_conditioner_panel(
+    input: Res<Input<KeyCode>>,
+    mut panel: Query<&mut Visibility, With<ConditionerPanel>>,
+) {
+    if input.just_pressed(KeyCode::F2) {
+        let mut visibility = panel.single_mut();
+        visibility.is_visible = !visibility.is_visible;
+    }
+}
+
+fn turn_knob_on_click(
+    conditioner: Res<LinkConditioner>,
+    mut buttons: Query<(&KnobButton, &Interaction, &mut UiColor), Changed<Interaction>>,
+) {
+    for (&KnobButton(knob, steps), interaction, mut color) in &mut buttons {
+        match interaction {
+            Interaction::Clicked => {
+                let mut conditions = conditioner.get();
+                knob.turn(&mut conditions, steps);
+                conditioner.set(conditions);
+            }
+            Interaction::Hovered => color.0 = HOVERED_BUTTON_COLOR,
+            Interaction::None => color.0 = BUTTON_COLOR,
+        }
+    }
+}
+
+fn update_knob_labels(
+    conditioner: Res<LinkConditioner>,
+    mut labels: Query<(&KnobLabel, &mut Text)>,
+) {
+    let conditions = conditioner.get();
+    for (&KnobLabel(knob), mut text) in &mut labels {
+        let description = knob.describe(&conditions);
+        if text.sections[0].value != description {
+            text.sections[0].value = description;
+        }
+    }
+}
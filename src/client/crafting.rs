@@ -0,0 +1,132 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+
+use crate::common::crafting::{Recipe, RecipeBook, RecipeId};
+use crate::common::inventory::Inventory;
+use crate::common::message::{ClientReliable, RenetClientExt};
+use crate::common::transport::NetClient;
+
+const BUTTON_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 0.6);
+const HOVERED_BUTTON_COLOR: Color = Color::rgba(0.2, 0.2, 0.2, 0.8);
+
+#[derive(Component)]
+struct CraftingPanel;
+
+#[derive(Component)]
+struct CraftButton(RecipeId);
+
+pub struct CraftingPlugin;
+
+impl Plugin for CraftingPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(RecipeBook::load())
+            .add_startup_system(setup_crafting_panel)
+            .add_system(toggle_crafting_panel)
+            .add_system(update_crafting_panel)
+            .add_system(craft_on_click);
+    }
+}
+
+fn describe(items: &BTreeMap<String, u32>) -> String {
+    items
+        .iter()
+        .map(|(item, amount)| format!("{amount} {item}"))
+        .collect::<Vec<_>>()
+        .join(" + ")
+}
+
+fn recipe_label(recipe: &Recipe) -> String {
+    format!(
+        "{} -> {}",
+        describe(&recipe.inputs),
+        describe(&recipe.outputs)
+    )
+}
+
+fn setup_crafting_panel(mut commands: Commands) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    right: Val::Px(10.0),
+                    top: Val::Px(10.0),
+                    ..default()
+                },
+                flex_direction: FlexDirection::ColumnReverse,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            visibility: Visibility { is_visible: false },
+            ..default()
+        })
+        .insert(CraftingPanel);
+}
+
+fn toggle_crafting_panel(
+    input: Res<Input<KeyCode>>,
+    mut panel: Query<&mut Visibility, With<CraftingPanel>>,
+) {
+    if input.just_pressed(KeyCode::C) {
+        let mut visibility = panel.single_mut();
+        visibility.is_visible = !visibility.is_visible;
+    }
+}
+
+fn update_crafting_panel(
+    mut commands: Commands,
+    inventory: Res<Inventory>,
+    recipes: Res<RecipeBook>,
+    asset_server: Res<AssetServer>,
+    panel: Query<Entity, With<CraftingPanel>>,
+) {
+    if !inventory.is_changed() {
+        return;
+    }
+
+    let panel = panel.single();
+    let font = asset_server.load("fonts/DejaVuSansMono.ttf");
+    commands.entity(panel).despawn_descendants();
+    commands.entity(panel).with_children(|commands| {
+        for (id, recipe) in recipes.iter() {
+            if !recipe.can_afford(&inventory) {
+                continue;
+            }
+            commands
+                .spawn_bundle(ButtonBundle {
+                    style: Style {
+                        margin: UiRect::all(Val::Px(2.0)),
+                        padding: UiRect::all(Val::Px(6.0)),
+                        ..default()
+                    },
+                    color: BUTTON_COLOR.into(),
+                    ..default()
+                })
+                .insert(CraftButton(id))
+                .with_children(|commands| {
+                    commands.spawn_bundle(TextBundle::from_section(
+                        recipe_label(recipe),
+                        TextStyle {
+                            font: font.clone(),
+                            font_size: 16.0,
+                            color: Color::WHITE,
+                        },
+                    ));
+                });
+        }
+    });
+}
+
+fn craft_on_click(
+    mut client: ResMut<NetClient>,
+    mut buttons: Query<(&CraftButton, &Interaction, &mut UiColor), Changed<Interaction>>,
+) {
+    for (&CraftButton(recipe), interaction, mut color) in &mut buttons {
+        match interaction {
+            Interaction::Clicked => client.send(ClientReliable::Craft(recipe)),
+            Interaction::Hovered => color.0 = HOVERED_BUTTON_COLOR,
+            Interaction::None => color.0 = BUTTON_COLOR,
+        }
+    }
+}
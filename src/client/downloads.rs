@@ -0,0 +1,126 @@
+use std::path::Path;
+use std::time::SystemTime;
+
+use bevy::prelude::*;
+use bevy::render::texture::{CompressedImageFormats, ImageType};
+
+use crate::common::message::{ClientReliable, RenetClientExt};
+use crate::common::tile::TileTexture;
+use crate::common::transfer::Downloads;
+use crate::common::transport::NetClient;
+
+/// Where saves downloaded from the server are written.
+const SAVE_DOWNLOAD_DIR: &str = "downloads";
+
+/// The images the server's tiles are drawn with, as last downloaded.
+#[derive(Default)]
+pub(crate) struct ServerTextures(pub(crate) Vec<TileTexture>);
+
+/// A copy of the player's save, just downloaded.
+pub(crate) struct DownloadedSave(pub(crate) Vec<u8>);
+
+#[derive(Component)]
+struct DownloadsLabel;
+
+/// Shows how far along every download is, like the world when joining a large game, and puts
+/// finished ones to use. F5 downloads the player's save.
+pub struct DownloadsPlugin;
+
+impl Plugin for DownloadsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(setup_label)
+            .add_system(update_label)
+            .add_system(load_server_textures)
+            .add_system(request_save)
+            .add_system(write_downloaded_save);
+    }
+}
+
+/// Fills in the handles tiles were drawn with while their images were on the way.
+fn load_server_textures(
+    textures: Res<ServerTextures>,
+    mut images: ResMut<Assets<Image>>,
+    asset_server: Res<AssetServer>,
+) {
+    if !textures.is_changed() {
+        return;
+    }
+    for texture in &textures.0 {
+        let extension = Path::new(&texture.path)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default();
+        match Image::from_buffer(
+            &texture.bytes,
+            ImageType::Extension(extension),
+            CompressedImageFormats::NONE,
+            true,
+        ) {
+            Ok(image) => {
+                let handle: Handle<Image> = asset_server.get_handle(texture.path.as_str());
+                images.set_untracked(handle, image);
+            }
+            Err(e) => warn!(path = %texture.path, error = %e, "Server sent an unreadable texture"),
+        }
+    }
+}
+
+fn request_save(mut client: ResMut<NetClient>, input: Res<Input<KeyCode>>) {
+    if input.just_pressed(KeyCode::F5) {
+        client.send(ClientReliable::DownloadSave);
+    }
+}
+
+fn write_downloaded_save(mut saves: EventReader<DownloadedSave>) {
+    for save in saves.iter() {
+        let seconds = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let path = Path::new(SAVE_DOWNLOAD_DIR).join(format!("save-{seconds}.ron"));
+        match std::fs::create_dir_all(SAVE_DOWNLOAD_DIR)
+            .and_then(|_| std::fs::write(&path, &save.0))
+        {
+            Ok(()) => info!(path = %path.display(), "Downloaded save"),
+            Err(e) => error!(path = %path.display(), error = %e, "Could not write save"),
+        }
+    }
+}
+
+fn setup_label(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let style = TextStyle {
+        font: asset_server.load("fonts/DejaVuSansMono.ttf"),
+        font_size: 20.0,
+        color: Color::WHITE,
+    };
+    commands
+        .spawn_bundle(TextBundle::from_section("", style).with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                bottom: Val::Px(40.0),
+                left: Val::Px(10.0),
+                ..default()
+            },
+            ..default()
+        }))
+        .insert(DownloadsLabel);
+}
+
+fn update_label(downloads: Res<Downloads>, mut label: Query<&mut Text, With<DownloadsLabel>>) {
+    if !downloads.is_changed() {
+        return;
+    }
+    let lines: Vec<_> = downloads
+        .progress()
+        .into_iter()
+        .map(|(kind, received, size)| {
+            format!(
+                "Downloading {}: {:.0}% of {:.1} KiB",
+                kind.name(),
+                received as f32 / size.max(1) as f32 * 100.0,
+                size as f32 / 1024.0
+            )
+        })
+        .collect();
+    label.single_mut().sections[0].value = lines.join("\n");
+}
@@ -0,0 +1,70 @@
+use bevy::math::Vec3Swizzles;
+use bevy::prelude::*;
+
+use super::{update_mouse_pos, Lobby, MousePos, Remote};
+use crate::common::collision::Aabb;
+use crate::common::message::{ClientReliable, RenetClientExt};
+use crate::common::player::{Dead, Health, HealthBar, HEALTH_BAR_SIZE, MAX_HEALTH};
+use crate::common::transport::NetClient;
+
+/// The remote player under the cursor, if any.
+#[derive(Default, Deref, DerefMut)]
+pub struct HoveredPlayer(pub Option<u64>);
+
+pub struct HealthPlugin;
+
+impl Plugin for HealthPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HoveredPlayer>()
+            .add_system(update_hovered_player.after(update_mouse_pos))
+            .add_system(attack_on_click.after(update_hovered_player))
+            .add_system(update_health_bars);
+    }
+}
+
+fn update_hovered_player(
+    mut hovered: ResMut<HoveredPlayer>,
+    mouse_pos: Res<MousePos>,
+    lobby: Res<Lobby>,
+    players: Query<&Transform, (With<Remote>, Without<Dead>)>,
+) {
+    let new = lobby
+        .players
+        .iter()
+        .find_map(|(&id, &entity)| match players.get(entity) {
+            Ok(tf) if Aabb::player(tf.translation.xy()).contains(mouse_pos.0) => Some(id),
+            _ => None,
+        });
+    if hovered.0 != new {
+        hovered.0 = new;
+    }
+}
+
+fn attack_on_click(
+    mut client: ResMut<NetClient>,
+    input: Res<Input<MouseButton>>,
+    hovered: Res<HoveredPlayer>,
+) {
+    if input.just_pressed(MouseButton::Left) {
+        if let Some(id) = hovered.0 {
+            client.send(ClientReliable::Attack(id));
+        }
+    }
+}
+
+fn update_health_bars(
+    players: Query<&Health, Changed<Health>>,
+    backgrounds: Query<&Parent>,
+    mut bars: Query<(&Parent, &mut Sprite), With<HealthBar>>,
+) {
+    for (background, mut sprite) in &mut bars {
+        let player = match backgrounds.get(background.get()) {
+            Ok(player) => player.get(),
+            Err(_) => continue,
+        };
+        if let Ok(health) = players.get(player) {
+            let fraction = (health.0 / MAX_HEALTH).clamp(0.0, 1.0);
+            sprite.custom_size = Some(Vec2::new(HEALTH_BAR_SIZE.x * fraction, HEALTH_BAR_SIZE.y));
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use bevy::input::mouse::MouseWheel;
+use bevy::prelude::*;
+
+use super::camera::zoom_modifier_held;
+use crate::common::inventory::Inventory;
+use crate::common::tile::{TileKind, TileRegistry};
+
+pub const HOTBAR_SLOTS: usize = 9;
+const SLOT_SIZE: f32 = 48.0;
+const SLOT_KEYS: [KeyCode; HOTBAR_SLOTS] = [
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+];
+
+/// The tile kinds the player can place, one per slot in registry order. Kinds that never drop
+/// an item, like lava, can't be held and so get no slot.
+#[derive(Default)]
+pub struct Hotbar {
+    pub slots: Vec<TileKind>,
+    pub selected: usize,
+}
+
+impl Hotbar {
+    pub fn selected_kind(&self) -> Option<TileKind> {
+        self.slots.get(self.selected).copied()
+    }
+}
+
+#[derive(Component)]
+struct HotbarRoot;
+
+#[derive(Component)]
+struct HotbarSlot(usize);
+
+#[derive(Component)]
+struct HotbarSwatch(usize);
+
+#[derive(Component)]
+struct HotbarCount(usize);
+
+pub struct HotbarPlugin;
+
+impl Plugin for HotbarPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Hotbar>()
+            .add_system(build_hotbar)
+            .add_system(select_hotbar_slot.after(build_hotbar))
+            .add_system(update_hotbar.after(select_hotbar_slot));
+    }
+}
+
+/// Fills the hotbar from the registry, again whenever the server sends its own.
+fn build_hotbar(
+    mut commands: Commands,
+    mut hotbar: ResMut<Hotbar>,
+    registry: Res<TileRegistry>,
+    asset_server: Res<AssetServer>,
+    roots: Query<Entity, With<HotbarRoot>>,
+) {
+    if !registry.is_changed() {
+        return;
+    }
+    for root in &roots {
+        commands.entity(root).despawn_recursive();
+    }
+    hotbar.slots = registry
+        .placeable()
+        .map(|(kind, _)| kind)
+        .take(HOTBAR_SLOTS)
+        .collect();
+    hotbar.selected = hotbar.selected.min(hotbar.slots.len().saturating_sub(1));
+
+    let font = asset_server.load("fonts/DejaVuSansMono.ttf");
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Auto),
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    bottom: Val::Px(10.0),
+                    ..default()
+                },
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            ..default()
+        })
+        .insert(HotbarRoot)
+        .with_children(|commands| {
+            for (slot, (_, def)) in registry.placeable().take(HOTBAR_SLOTS).enumerate() {
+                commands
+                    .spawn_bundle(NodeBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(SLOT_SIZE), Val::Px(SLOT_SIZE)),
+                            margin: UiRect::all(Val::Px(2.0)),
+                            padding: UiRect::all(Val::Px(4.0)),
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .insert(HotbarSlot(slot))
+                    .with_children(|commands| {
+                        commands
+                            .spawn_bundle(NodeBundle {
+                                style: Style {
+                                    size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                                    ..default()
+                                },
+                                image: match &def.texture {
+                                    Some(path) => UiImage(asset_server.get_handle(path.as_str())),
+                                    None => default(),
+                                },
+                                ..default()
+                            })
+                            .insert(HotbarSwatch(slot));
+                        commands
+                            .spawn_bundle(TextBundle {
+                                style: Style {
+                                    position_type: PositionType::Absolute,
+                                    position: UiRect {
+                                        right: Val::Px(4.0),
+                                        bottom: Val::Px(2.0),
+                                        ..default()
+                                    },
+                                    ..default()
+                                },
+                                ..TextBundle::from_section(
+                                    "",
+                                    TextStyle {
+                                        font: font.clone(),
+                                        font_size: 16.0,
+                                        color: Color::WHITE,
+                                    },
+                                )
+                            })
+                            .insert(HotbarCount(slot));
+                    });
+            }
+        });
+}
+
+fn select_hotbar_slot(
+    mut hotbar: ResMut<Hotbar>,
+    input: Res<Input<KeyCode>>,
+    mut scroll: EventReader<MouseWheel>,
+) {
+    for (slot, &key) in SLOT_KEYS.iter().enumerate() {
+        if input.just_pressed(key) && slot < hotbar.slots.len() {
+            hotbar.selected = slot;
+        }
+    }
+
+    let slots = hotbar.slots.len();
+    if slots == 0 || zoom_modifier_held(&input) {
+        scroll.clear();
+        return;
+    }
+    for event in scroll.iter() {
+        if event.y > 0.0 {
+            hotbar.selected = (hotbar.selected + slots - 1) % slots;
+        } else if event.y < 0.0 {
+            hotbar.selected = (hotbar.selected + 1) % slots;
+        }
+    }
+}
+
+fn update_hotbar(
+    hotbar: Res<Hotbar>,
+    inventory: Res<Inventory>,
+    registry: Res<TileRegistry>,
+    mut slots: Query<(&HotbarSlot, &mut UiColor)>,
+    mut swatches: Query<(&HotbarSwatch, &mut UiColor), Without<HotbarSlot>>,
+    mut counts: Query<(&HotbarCount, &mut Text)>,
+    built: Query<(), Added<HotbarSlot>>,
+) {
+    if !hotbar.is_changed() && !inventory.is_changed() && built.is_empty() {
+        return;
+    }
+
+    for (&HotbarSlot(slot), mut color) in &mut slots {
+        color.0 = if slot == hotbar.selected {
+            Color::GOLD
+        } else {
+            Color::rgba(0.0, 0.0, 0.0, 0.5)
+        };
+    }
+    for (&HotbarSwatch(slot), mut color) in &mut swatches {
+        let def = match hotbar.slots.get(slot).and_then(|&kind| registry.get(kind)) {
+            Some(def) => def,
+            None => continue,
+        };
+        let mut swatch = match def.texture {
+            Some(_) => Color::WHITE,
+            None => def.color,
+        };
+        if inventory.count(&def.name) == 0 {
+            swatch.set_a(0.3);
+        }
+        color.0 = swatch;
+    }
+    for (&HotbarCount(slot), mut text) in &mut counts {
+        if let Some(def) = hotbar.slots.get(slot).and_then(|&kind| registry.get(kind)) {
+            text.sections[0].value = inventory.count(&def.name).to_string();
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use bevy::prelude::*;
+
+use crate::common::transport::{NetClient, NetworkStats};
+
+#[derive(Component)]
+struct StatsOverlay;
+
+/// An overlay, toggled with F3, showing round trip time, packet loss and what each channel and
+/// message type costs.
+pub struct NetworkStatsPlugin;
+
+impl Plugin for NetworkStatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(setup_overlay)
+            .add_system(toggle_overlay)
+            .add_system(update_overlay.after(toggle_overlay));
+    }
+}
+
+fn setup_overlay(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let style = TextStyle {
+        font: asset_server.load("fonts/DejaVuSansMono.ttf"),
+        font_size: 14.0,
+        color: Color::WHITE,
+    };
+    commands
+        .spawn_bundle(TextBundle {
+            visibility: Visibility { is_visible: false },
+            ..TextBundle::from_section("", style).with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Px(40.0),
+                    left: Val::Px(10.0),
+                    ..default()
+                },
+                ..default()
+            })
+        })
+        .insert(StatsOverlay);
+}
+
+fn toggle_overlay(
+    input: Res<Input<KeyCode>>,
+    mut overlay: Query<&mut Visibility, With<StatsOverlay>>,
+) {
+    if input.just_pressed(KeyCode::F3) {
+        let mut visibility = overlay.single_mut();
+        visibility.is_visible = !visibility.is_visible;
+    }
+}
+
+fn update_overlay(
+    client: Res<NetClient>,
+    stats: Res<NetworkStats>,
+    mut overlay: Query<(&mut Text, &Visibility), With<StatsOverlay>>,
+) {
+    let (mut text, visibility) = overlay.single_mut();
+    if !visibility.is_visible {
+        return;
+    }
+    let report = match stats.get(client.client_id()) {
+        Some(connection) => connection.report(),
+        None => "Not connected".to_string(),
+    };
+    if text.sections[0].value != report {
+        text.sections[0].value = report;
+    }
+}
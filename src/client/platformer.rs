@@ -0,0 +1,147 @@
+use std::collections::VecDeque;
+
+use bevy::math::Vec3Swizzles;
+use bevy::prelude::*;
+
+use super::Remote;
+use crate::common::message::{ClientUnreliable, RenetClientExt};
+use crate::common::physics::{platformer_step, MoveInput, MovementMode, PlatformerBody, FIXED_DT};
+use crate::common::player::{Dead, Player};
+use crate::common::rules::GameRules;
+use crate::common::tile::{TileRegistry, Tiles};
+use crate::common::transport::NetClient;
+
+/// Number of recent inputs repeated in every input message.
+const INPUT_REDUNDANCY: usize = 8;
+/// Most ticks simulated in one frame, so a long hitch doesn't stall the game further.
+const MAX_TICKS_PER_FRAME: u32 = 8;
+/// Most inputs kept around waiting for the server to acknowledge them.
+const MAX_UNACKED_INPUTS: usize = 120;
+
+/// Authoritative platformer state of the local player, as sent by the server.
+pub struct PlatformerCorrection {
+    pub tick: u32,
+    pub pos: Vec2,
+    pub body: PlatformerBody,
+}
+
+#[derive(Default)]
+struct Prediction {
+    tick: u32,
+    /// Latest tick the server has confirmed.
+    acked: u32,
+    accumulator: f32,
+    jump_queued: bool,
+    body: PlatformerBody,
+    /// Inputs the server hasn't acknowledged yet, replayed on top of every correction.
+    history: VecDeque<(u32, MoveInput)>,
+}
+
+pub struct PlatformerPlugin;
+
+impl Plugin for PlatformerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Prediction>()
+            .add_system(reconcile_platformer)
+            .add_system(predict_platformer.after(reconcile_platformer));
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn predict_platformer(
+    mut player: Query<(&mut Transform, Option<&Dead>), (With<Player>, Without<Remote>)>,
+    mut prediction: ResMut<Prediction>,
+    mut client: ResMut<NetClient>,
+    input: Res<Input<KeyCode>>,
+    time: Res<Time>,
+    rules: Res<GameRules>,
+    tiles: Res<Tiles>,
+    registry: Res<TileRegistry>,
+) {
+    if rules.movement != MovementMode::Platformer {
+        return;
+    }
+    let (mut tf, dead) = match player.get_single_mut() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+    let prediction = &mut *prediction;
+
+    if input.any_just_pressed([KeyCode::Space, KeyCode::W]) {
+        prediction.jump_queued = true;
+    }
+
+    prediction.accumulator += time.delta_seconds();
+    let mut ticks = 0;
+    let mut pos = tf.translation.xy();
+    while prediction.accumulator >= FIXED_DT && ticks < MAX_TICKS_PER_FRAME {
+        prediction.accumulator -= FIXED_DT;
+        prediction.tick += 1;
+        ticks += 1;
+
+        let jump = std::mem::take(&mut prediction.jump_queued);
+        let tick_input = if dead.is_some() {
+            MoveInput::default()
+        } else {
+            MoveInput {
+                left: input.pressed(KeyCode::A),
+                right: input.pressed(KeyCode::D),
+                jump,
+            }
+        };
+        prediction.history.push_back((prediction.tick, tick_input));
+        if prediction.history.len() > MAX_UNACKED_INPUTS {
+            prediction.history.pop_front();
+        }
+        pos = platformer_step(&tiles, &registry, pos, &mut prediction.body, tick_input);
+    }
+    if ticks == MAX_TICKS_PER_FRAME {
+        prediction.accumulator = 0.0;
+    }
+    if ticks == 0 {
+        return;
+    }
+
+    tf.translation.x = pos.x;
+    tf.translation.y = pos.y;
+    let skip = prediction.history.len().saturating_sub(INPUT_REDUNDANCY);
+    client.send(ClientUnreliable::PlatformerInput {
+        tick: prediction.tick,
+        inputs: prediction
+            .history
+            .iter()
+            .skip(skip)
+            .map(|&(_, input)| input)
+            .collect(),
+    });
+}
+
+fn reconcile_platformer(
+    mut corrections: EventReader<PlatformerCorrection>,
+    mut player: Query<&mut Transform, (With<Player>, Without<Remote>)>,
+    mut prediction: ResMut<Prediction>,
+    tiles: Res<Tiles>,
+    registry: Res<TileRegistry>,
+) {
+    let correction = match corrections.iter().max_by_key(|correction| correction.tick) {
+        Some(correction) if correction.tick > prediction.acked => correction,
+        _ => return,
+    };
+    let prediction = &mut *prediction;
+    prediction.acked = correction.tick;
+    // The server simulates us standing still when our inputs fall behind, carry on from there.
+    prediction.tick = prediction.tick.max(correction.tick);
+    prediction
+        .history
+        .retain(|&(tick, _)| tick > correction.tick);
+
+    let mut pos = correction.pos;
+    prediction.body = correction.body;
+    for &(_, input) in &prediction.history {
+        pos = platformer_step(&tiles, &registry, pos, &mut prediction.body, input);
+    }
+
+    let mut tf = player.single_mut();
+    tf.translation.x = pos.x;
+    tf.translation.y = pos.y;
+}
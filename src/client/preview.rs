@@ -0,0 +1,180 @@
+use bevy::math::Vec3Swizzles;
+use bevy::prelude::*;
+
+use super::hotbar::Hotbar;
+use super::{update_mouse_pos, CurrentGridCoord, Remote};
+use crate::common::inventory::Inventory;
+use crate::common::player::Player;
+use crate::common::rules::GameRules;
+use crate::common::tile::{TileKind, TileRegistry, Tiles, TILE_SIZE};
+
+const OUTLINE_WIDTH: f32 = 3.0;
+const GHOST_ALPHA: f32 = 0.4;
+const INVALID_COLOR: Color = Color::rgba(1.0, 0.1, 0.1, 0.9);
+const BREAK_HIGHLIGHT_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.25);
+
+/// What a right click would place at the hovered grid cell, and whether it's allowed.
+#[derive(Default, PartialEq)]
+pub struct Placement {
+    pub kind: Option<TileKind>,
+    /// Whether the player has a block of the selected kind to place.
+    pub affordable: bool,
+    /// Whether the hovered cell is within the local player's reach.
+    pub in_reach: bool,
+    /// Whether the hovered cell is by a spawn point, where nothing can be built or mined.
+    pub protected: bool,
+    pub valid: bool,
+}
+
+#[derive(Component)]
+struct PlacementGhost;
+
+#[derive(Component)]
+struct InvalidOutline;
+
+#[derive(Component)]
+struct BreakHighlight;
+
+pub struct PreviewPlugin;
+
+impl Plugin for PreviewPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Placement>()
+            .add_startup_system(setup_preview)
+            .add_system(update_placement.after(update_mouse_pos))
+            .add_system(update_preview.after(update_placement));
+    }
+}
+
+fn cell_sprite(color: Color, size: Vec2, translation: Vec3) -> SpriteBundle {
+    SpriteBundle {
+        sprite: Sprite {
+            color,
+            custom_size: Some(size),
+            ..default()
+        },
+        transform: Transform::from_translation(translation),
+        ..default()
+    }
+}
+
+fn setup_preview(mut commands: Commands) {
+    let hidden = Visibility { is_visible: false };
+    commands
+        .spawn_bundle(SpriteBundle {
+            visibility: hidden.clone(),
+            ..cell_sprite(Color::NONE, Vec2::splat(TILE_SIZE), Vec3::ZERO)
+        })
+        .insert(PlacementGhost);
+    commands
+        .spawn_bundle(SpriteBundle {
+            visibility: hidden.clone(),
+            ..cell_sprite(BREAK_HIGHLIGHT_COLOR, Vec2::splat(TILE_SIZE), Vec3::ZERO)
+        })
+        .insert(BreakHighlight);
+
+    let edge = TILE_SIZE / 2.0 - OUTLINE_WIDTH / 2.0;
+    let horizontal = Vec2::new(TILE_SIZE, OUTLINE_WIDTH);
+    let vertical = Vec2::new(OUTLINE_WIDTH, TILE_SIZE);
+    commands
+        .spawn_bundle(SpatialBundle {
+            visibility: hidden,
+            ..default()
+        })
+        .insert(InvalidOutline)
+        .with_children(|commands| {
+            for (size, offset) in [
+                (horizontal, Vec2::new(0.0, edge)),
+                (horizontal, Vec2::new(0.0, -edge)),
+                (vertical, Vec2::new(edge, 0.0)),
+                (vertical, Vec2::new(-edge, 0.0)),
+            ] {
+                commands.spawn_bundle(cell_sprite(INVALID_COLOR, size, offset.extend(0.0)));
+            }
+        });
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_placement(
+    mut placement: ResMut<Placement>,
+    grid_coord: Res<CurrentGridCoord>,
+    player: Query<&Transform, (With<Player>, Without<Remote>)>,
+    tiles: Res<Tiles>,
+    hotbar: Res<Hotbar>,
+    inventory: Res<Inventory>,
+    registry: Res<TileRegistry>,
+    rules: Res<GameRules>,
+) {
+    let kind = hotbar.selected_kind();
+    let affordable = matches!(
+        kind.and_then(|kind| registry.get(kind)),
+        Some(def) if inventory.count(&def.name) > 0
+    );
+    let in_reach = matches!(
+        player.get_single(),
+        Ok(tf) if rules.in_reach(tf.translation.xy(), grid_coord.0)
+    );
+    let protected = rules.is_protected(&tiles, &registry, grid_coord.0);
+    let new = Placement {
+        kind,
+        affordable,
+        in_reach,
+        protected,
+        valid: affordable && in_reach && !protected && !tiles.contains_key(&grid_coord.0),
+    };
+    if *placement != new {
+        *placement = new;
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn update_preview(
+    placement: Res<Placement>,
+    grid_coord: Res<CurrentGridCoord>,
+    tiles: Res<Tiles>,
+    registry: Res<TileRegistry>,
+    mut ghost: Query<
+        (&mut Transform, &mut Visibility, &mut Sprite),
+        (
+            With<PlacementGhost>,
+            Without<InvalidOutline>,
+            Without<BreakHighlight>,
+        ),
+    >,
+    mut outline: Query<
+        (&mut Transform, &mut Visibility),
+        (
+            With<InvalidOutline>,
+            Without<PlacementGhost>,
+            Without<BreakHighlight>,
+        ),
+    >,
+    mut highlight: Query<
+        (&mut Transform, &mut Visibility),
+        (
+            With<BreakHighlight>,
+            Without<PlacementGhost>,
+            Without<InvalidOutline>,
+        ),
+    >,
+) {
+    let translation = (grid_coord.as_vec2() * TILE_SIZE).extend(0.2);
+
+    let (mut tf, mut visibility, mut sprite) = ghost.single_mut();
+    let def = placement.kind.and_then(|kind| registry.get(kind));
+    visibility.is_visible = placement.valid && def.is_some();
+    if let Some(def) = def {
+        sprite.color = *def.color.clone().set_a(GHOST_ALPHA);
+    }
+    tf.translation = translation;
+
+    // Only worth pointing out while the player is actually holding something to place.
+    let (mut tf, mut visibility) = outline.single_mut();
+    visibility.is_visible = placement.affordable && !placement.valid;
+    tf.translation = translation;
+
+    let (mut tf, mut visibility) = highlight.single_mut();
+    visibility.is_visible =
+        placement.in_reach && !placement.protected && tiles.contains_key(&grid_coord.0);
+    tf.translation = translation;
+}
@@ -0,0 +1,303 @@
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use bevy::log::LogPlugin;
+use bevy::prelude::*;
+use bevy_renet::renet::{NetworkInfo, RenetError};
+
+use super::camera::CameraPlugin;
+use super::downloads::DownloadsPlugin;
+use super::health::HealthPlugin;
+use super::spectator::SpectatorPlugin;
+use super::team::TeamPlugin;
+use super::{update_cracks, Lobby, MousePos, NetworkPlugin, WorldSync};
+use crate::common::message::{
+    message_name, NetworkIds, SendOverRenet, ServerBlocking, ServerUnreliable,
+};
+use crate::common::player::ConnectInfo;
+use crate::common::replay::{load_replay, Recipient, ReplayEntry, ReplayEvent};
+use crate::common::tile::Tiles;
+use crate::common::transfer::{Chunk, Downloads, TransferKind};
+use crate::common::transport::{ClientTransport, NetClient};
+
+const SEEK_SECONDS: f64 = 5.0;
+const MIN_SPEED: f64 = 0.125;
+const MAX_SPEED: f64 = 16.0;
+
+/// Recorded server messages waiting to be picked up, one queue per channel.
+#[derive(Clone, Default)]
+struct Feed(Arc<Mutex<[VecDeque<Vec<u8>>; 3]>>);
+
+impl Feed {
+    fn queues(&self) -> MutexGuard<'_, [VecDeque<Vec<u8>>; 3]> {
+        self.0.lock().expect("No one panics while holding the feed")
+    }
+}
+
+/// Stands in for the connection to a server, handing out recorded messages instead.
+struct ReplayClient(Feed);
+
+impl ClientTransport for ReplayClient {
+    fn update(&mut self, _delta: Duration) -> Result<(), RenetError> {
+        Ok(())
+    }
+
+    fn send_packets(&mut self) -> Result<(), RenetError> {
+        Ok(())
+    }
+
+    fn client_id(&self) -> u64 {
+        u64::MAX
+    }
+
+    fn is_connected(&self) -> bool {
+        true
+    }
+
+    fn network_info(&self) -> NetworkInfo {
+        NetworkInfo::default()
+    }
+
+    fn disconnect(&mut self) {}
+
+    fn receive_message(&mut self, channel_id: u8) -> Option<Vec<u8>> {
+        self.0.queues().get_mut(channel_id as usize)?.pop_front()
+    }
+
+    /// There's no one to hear what the viewer does.
+    fn send_message(&mut self, _channel_id: u8, _message: Vec<u8>) {}
+}
+
+/// A recording being played back.
+struct Replay {
+    entries: Vec<ReplayEntry>,
+    /// The first entry not played yet.
+    next: usize,
+    /// How far into the recording playback is, in seconds.
+    time: f64,
+    speed: f64,
+    paused: bool,
+    /// The first client sent the world, whose copy of it and its textures is shown. Copies sent
+    /// to later clients are skipped.
+    first_client: Option<u64>,
+    /// Set when seeking backwards, the world is rebuilt from the start of the recording.
+    rewind: bool,
+    feed: Feed,
+}
+
+impl Replay {
+    fn duration(&self) -> f64 {
+        self.entries.last().map_or(0.0, |entry| entry.time)
+    }
+
+    /// Whether a spectator that watched from the start would have received this message.
+    fn shows(&mut self, to: Recipient, channel_id: u8, message: &[u8]) -> bool {
+        match to {
+            Recipient::All | Recipient::AllExcept(_) => true,
+            // Messages for a single client are its own state, except for the world, textures and
+            // rules everyone is sent on joining.
+            Recipient::One(client_id) => {
+                match (channel_id, message_name(true, channel_id, message)) {
+                    (ServerBlocking::CHANNEL_ID, "SyncRules") => true,
+                    (ServerUnreliable::CHANNEL_ID, "TransferChunk") => {
+                        match bincode::deserialize(message) {
+                            Ok(ServerUnreliable::TransferChunk(Chunk {
+                                kind: TransferKind::World | TransferKind::Textures,
+                                ..
+                            })) => *self.first_client.get_or_insert(client_id) == client_id,
+                            _ => false,
+                        }
+                    }
+                    _ => false,
+                }
+            }
+        }
+    }
+}
+
+#[derive(Component)]
+struct ReplayLabel;
+
+/// Plays back a replay recorded by the server, as a spectator that saw everything.
+///
+/// Space pauses, Left and Right seek, Up and Down change the speed and Home starts over.
+pub fn replay() {
+    let path = std::env::args()
+        .nth(2)
+        .unwrap_or_else(|| panic!("Usage: replay <file>"));
+    let entries = load_replay(Path::new(&path));
+    info!(path = %path, entries = entries.len(), "Playing a replay");
+
+    let feed = Feed::default();
+    App::new()
+        .init_resource::<MousePos>()
+        .insert_resource(ClearColor(Color::rgb(0.35, 0.1, 0.7)))
+        .insert_resource(WindowDescriptor {
+            title: "Making a multiplayer game in Rust - Replay".to_string(),
+            width: 2560. / 2.4,
+            height: 1440. / 2.4,
+            ..Default::default()
+        })
+        // Logging is set up in `main`, for every role alike.
+        .add_plugins_with(DefaultPlugins, |group| group.disable::<LogPlugin>())
+        .add_plugin(NetworkPlugin)
+        .add_plugin(CameraPlugin)
+        .add_plugin(HealthPlugin)
+        .add_plugin(TeamPlugin)
+        .add_plugin(SpectatorPlugin)
+        .add_plugin(DownloadsPlugin)
+        .insert_resource(NetClient::new(ReplayClient(feed.clone())))
+        .insert_resource(ConnectInfo {
+            name: "replay".to_string(),
+            spectator: true,
+            compression: Vec::new(),
+        })
+        .insert_resource(Replay {
+            entries,
+            next: 0,
+            time: 0.0,
+            speed: 1.0,
+            paused: false,
+            first_client: None,
+            rewind: false,
+            feed,
+        })
+        .add_startup_system(setup)
+        .add_system_to_stage(CoreStage::PreUpdate, play_replay)
+        .add_system(control_replay)
+        .add_system(update_label)
+        .add_system(update_cracks)
+        .run();
+}
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn_bundle(Camera2dBundle::default());
+    let style = TextStyle {
+        font: asset_server.load("fonts/DejaVuSansMono.ttf"),
+        font_size: 20.0,
+        color: Color::WHITE,
+    };
+    commands
+        .spawn_bundle(TextBundle::from_section("", style).with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                bottom: Val::Px(10.0),
+                left: Val::Px(10.0),
+                ..default()
+            },
+            ..default()
+        }))
+        .insert(ReplayLabel);
+}
+
+fn control_replay(mut replay: ResMut<Replay>, input: Res<Input<KeyCode>>) {
+    if input.just_pressed(KeyCode::Space) {
+        replay.paused = !replay.paused;
+    }
+    if input.just_pressed(KeyCode::Up) {
+        replay.speed = (replay.speed * 2.0).min(MAX_SPEED);
+    }
+    if input.just_pressed(KeyCode::Down) {
+        replay.speed = (replay.speed / 2.0).max(MIN_SPEED);
+    }
+    if input.just_pressed(KeyCode::Right) {
+        replay.time = (replay.time + SEEK_SECONDS).min(replay.duration());
+    }
+    if input.just_pressed(KeyCode::Left) {
+        replay.time = (replay.time - SEEK_SECONDS).max(0.0);
+        replay.rewind = true;
+    }
+    if input.just_pressed(KeyCode::Home) {
+        replay.time = 0.0;
+        replay.rewind = true;
+    }
+}
+
+/// Hands the client every recorded message that is due.
+#[allow(clippy::too_many_arguments)]
+fn play_replay(
+    mut commands: Commands,
+    mut replay: ResMut<Replay>,
+    mut lobby: ResMut<Lobby>,
+    mut network_ids: ResMut<NetworkIds>,
+    mut tiles: ResMut<Tiles>,
+    mut downloads: ResMut<Downloads>,
+    mut sync: ResMut<WorldSync>,
+    time: Res<Time>,
+) {
+    if replay.rewind {
+        for (_, player) in lobby.players.drain() {
+            commands.entity(player).despawn_recursive();
+        }
+        lobby.indices.clear();
+        for (_, tile) in network_ids.drain() {
+            commands.entity(tile).despawn_recursive();
+        }
+        tiles.clear();
+        *downloads = default();
+        *sync = default();
+        replay.next = 0;
+        replay.first_client = None;
+        replay.rewind = false;
+        return;
+    }
+    if !replay.paused {
+        replay.time =
+            (replay.time + time.delta_seconds_f64() * replay.speed).min(replay.duration());
+    }
+
+    let replay = &mut *replay;
+    let feed = replay.feed.clone();
+    let mut queues = feed.queues();
+    while let Some(entry) = replay.entries.get(replay.next) {
+        if entry.time > replay.time {
+            break;
+        }
+        let event = entry.event.clone();
+        replay.next += 1;
+        match event {
+            ReplayEvent::Sent {
+                to,
+                channel_id,
+                message,
+            } => {
+                if !replay.shows(to, channel_id, &message) {
+                    continue;
+                }
+                queues[channel_id as usize].push_back(message);
+            }
+            ReplayEvent::Received {
+                client_id,
+                channel_id,
+                message,
+            } => {
+                debug!(
+                    client_id,
+                    message = message_name(false, channel_id, &message),
+                    "Client sent"
+                );
+            }
+            ReplayEvent::Connected(client_id, user_data) => match user_data.as_slice().try_into() {
+                Ok(user_data) => {
+                    let info = ConnectInfo::decode(user_data);
+                    info!(client_id, name = %info.name, "Client connected");
+                }
+                Err(_) => info!(client_id, "Client connected"),
+            },
+            ReplayEvent::Disconnected(client_id) => info!(client_id, "Client disconnected"),
+        }
+    }
+}
+
+fn update_label(replay: Res<Replay>, mut label: Query<&mut Text, With<ReplayLabel>>) {
+    let mut text = label.single_mut();
+    text.sections[0].value = format!(
+        "{:.1}/{:.1}s  x{}{}",
+        replay.time,
+        replay.duration(),
+        replay.speed,
+        if replay.paused { "  paused" } else { "" }
+    );
+}
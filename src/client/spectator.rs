@@ -0,0 +1,155 @@
+use bevy::prelude::*;
+
+use super::Lobby;
+use crate::common::player::{ConnectInfo, Dead, PlayerName};
+
+const FREE_FLY_SPEED: f32 = 800.0;
+/// How quickly the camera catches up with a followed player, higher is snappier.
+const FOLLOW_SMOOTHING: f32 = 8.0;
+
+/// What a spectator's camera is doing.
+#[derive(Default)]
+struct SpectatorCamera {
+    /// The player being followed, or `None` when flying freely.
+    follow: Option<u64>,
+}
+
+#[derive(Component)]
+struct SpectatorLabel;
+
+/// Free-fly camera for spectators, Tab cycles through players to follow and WASD or Escape
+/// goes back to flying.
+pub struct SpectatorPlugin;
+
+impl Plugin for SpectatorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SpectatorCamera>()
+            .add_startup_system(setup_label)
+            .add_system(choose_target)
+            .add_system(move_camera.after(choose_target))
+            .add_system(update_label.after(choose_target));
+    }
+}
+
+fn setup_label(mut commands: Commands, asset_server: Res<AssetServer>, info: Res<ConnectInfo>) {
+    if !info.spectator {
+        return;
+    }
+    let style = TextStyle {
+        font: asset_server.load("fonts/DejaVuSansMono.ttf"),
+        font_size: 20.0,
+        color: Color::WHITE,
+    };
+    commands
+        .spawn_bundle(TextBundle::from_section("", style).with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                top: Val::Px(10.0),
+                left: Val::Px(10.0),
+                ..default()
+            },
+            ..default()
+        }))
+        .insert(SpectatorLabel);
+}
+
+fn choose_target(
+    mut spectator: ResMut<SpectatorCamera>,
+    input: Res<Input<KeyCode>>,
+    lobby: Res<Lobby>,
+    info: Res<ConnectInfo>,
+) {
+    if !info.spectator {
+        return;
+    }
+    if let Some(id) = spectator.follow {
+        if !lobby.players.contains_key(&id) {
+            spectator.follow = None;
+        }
+    }
+
+    if input.just_pressed(KeyCode::Tab) {
+        let mut ids: Vec<u64> = lobby.players.keys().copied().collect();
+        ids.sort_unstable();
+        spectator.follow = match spectator.follow {
+            Some(current) => ids.iter().copied().find(|&id| id > current),
+            None => None,
+        }
+        .or_else(|| ids.first().copied());
+    } else if input.any_just_pressed([
+        KeyCode::Escape,
+        KeyCode::W,
+        KeyCode::A,
+        KeyCode::S,
+        KeyCode::D,
+    ]) && spectator.follow.is_some()
+    {
+        spectator.follow = None;
+    }
+}
+
+fn move_camera(
+    spectator: Res<SpectatorCamera>,
+    lobby: Res<Lobby>,
+    players: Query<&Transform, Without<Camera>>,
+    mut camera: Query<(&mut Transform, &OrthographicProjection), With<Camera>>,
+    input: Res<Input<KeyCode>>,
+    info: Res<ConnectInfo>,
+    time: Res<Time>,
+) {
+    if !info.spectator {
+        return;
+    }
+    let (mut camera, projection) = camera.single_mut();
+    match spectator.follow.and_then(|id| lobby.players.get(&id)) {
+        Some(&player) => {
+            if let Ok(target) = players.get(player) {
+                let t = 1.0 - (-FOLLOW_SMOOTHING * time.delta_seconds()).exp();
+                let z = camera.translation.z;
+                camera.translation = camera.translation.lerp(target.translation, t);
+                camera.translation.z = z;
+            }
+        }
+        None => {
+            let mut direction = Vec2::ZERO;
+            if input.pressed(KeyCode::W) {
+                direction.y += 1.0;
+            }
+            if input.pressed(KeyCode::A) {
+                direction.x -= 1.0;
+            }
+            if input.pressed(KeyCode::S) {
+                direction.y -= 1.0;
+            }
+            if input.pressed(KeyCode::D) {
+                direction.x += 1.0;
+            }
+            let delta = direction * FREE_FLY_SPEED * projection.scale * time.delta_seconds();
+            camera.translation += delta.extend(0.0);
+        }
+    }
+}
+
+fn update_label(
+    spectator: Res<SpectatorCamera>,
+    lobby: Res<Lobby>,
+    players: Query<(&PlayerName, Option<&Dead>)>,
+    mut label: Query<&mut Text, With<SpectatorLabel>>,
+) {
+    let mut text = match label.get_single_mut() {
+        Ok(text) => text,
+        Err(_) => return,
+    };
+    let target = spectator
+        .follow
+        .and_then(|id| lobby.players.get(&id))
+        .and_then(|&player| players.get(player).ok());
+    let value = match target {
+        Some((name, None)) => format!("Spectating {} (Tab: next, Esc: free camera)", name.0),
+        Some((name, Some(_))) => format!("Spectating {}, respawning...", name.0),
+        None => "Free camera (WASD: move, Tab: follow a player)".to_string(),
+    };
+    if text.sections[0].value != value {
+        text.sections[0].value = value;
+    }
+}
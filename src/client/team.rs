@@ -0,0 +1,102 @@
+use bevy::prelude::*;
+
+use super::Remote;
+use crate::common::message::{ClientReliable, RenetClientExt};
+use crate::common::player::{PlayerName, PlayerTeam, PLAYER_SIZE};
+use crate::common::rules::GameRules;
+use crate::common::team::TeamId;
+use crate::common::transport::NetClient;
+
+const NAME_TAG_SIZE: f32 = 20.0;
+
+#[derive(Component)]
+struct NameTag;
+
+pub struct TeamPlugin;
+
+impl Plugin for TeamPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(request_team_change)
+            .add_system(update_name_tags);
+    }
+}
+
+/// Cycles the local player through the server's teams with T.
+fn request_team_change(
+    mut client: ResMut<NetClient>,
+    input: Res<Input<KeyCode>>,
+    rules: Res<GameRules>,
+    player: Query<&PlayerTeam, Without<Remote>>,
+) {
+    if !input.just_pressed(KeyCode::T) || rules.teams.is_empty() {
+        return;
+    }
+    let next = match player.get_single() {
+        Ok(PlayerTeam(Some(team))) => (team.0 as usize + 1) % rules.teams.len(),
+        _ => 0,
+    };
+    client.send(ClientReliable::ChangeTeam(TeamId(next as u8)));
+}
+
+fn name_tag_text(rules: &GameRules, name: &str, team: Option<TeamId>) -> String {
+    match team.and_then(|team| rules.team(team)) {
+        Some(def) => format!("[{}] {}", def.name, name),
+        None => name.to_string(),
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn update_name_tags(
+    mut commands: Commands,
+    players: Query<(
+        Entity,
+        &PlayerName,
+        &PlayerTeam,
+        Option<&Children>,
+        ChangeTrackers<PlayerName>,
+        ChangeTrackers<PlayerTeam>,
+    )>,
+    mut tags: Query<&mut Text, With<NameTag>>,
+    rules: Res<GameRules>,
+    asset_server: Res<AssetServer>,
+) {
+    for (player, name, team, children, name_changed, team_changed) in &players {
+        if !rules.is_changed() && !name_changed.is_changed() && !team_changed.is_changed() {
+            continue;
+        }
+        let value = name_tag_text(&rules, name, team.0);
+        let color = match team.and_then(|team| rules.team(team)) {
+            Some(def) => def.color,
+            None => Color::WHITE,
+        };
+
+        let tag = children
+            .into_iter()
+            .flatten()
+            .find(|&&child| tags.contains(child));
+        match tag {
+            Some(&tag) => {
+                let section = &mut tags.get_mut(tag).unwrap().sections[0];
+                section.value = value;
+                section.style.color = color;
+            }
+            None => {
+                let style = TextStyle {
+                    font: asset_server.load("fonts/DejaVuSansMono.ttf"),
+                    font_size: NAME_TAG_SIZE,
+                    color,
+                };
+                commands.entity(player).with_children(|commands| {
+                    commands
+                        .spawn_bundle(Text2dBundle {
+                            text: Text::from_section(value, style)
+                                .with_alignment(TextAlignment::CENTER),
+                            transform: Transform::from_xyz(0.0, PLAYER_SIZE / 2.0 + 30.0, 0.5),
+                            ..default()
+                        })
+                        .insert(NameTag);
+                });
+            }
+        }
+    }
+}
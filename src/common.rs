@@ -4,6 +4,16 @@ use bevy_renet::renet::RenetError;
 pub mod tile;
 pub mod message;
 pub mod player;
+pub mod inventory;
+pub mod crafting;
+pub mod collision;
+pub mod physics;
+pub mod rules;
+pub mod team;
+pub mod transport;
+pub mod replay;
+pub mod transfer;
+pub mod bits;
 
 pub fn panic_on_error(mut renet_error: EventReader<RenetError>) {
     for e in renet_error.iter() {
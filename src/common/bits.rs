@@ -0,0 +1,60 @@
+//! Packs values into exactly as many bits as they need, for messages sent so often that every
+//! byte counts.
+
+/// The number of bits it takes to write every value up to `max`.
+pub fn bits_needed(max: u32) -> u32 {
+    u32::BITS - max.leading_zeros()
+}
+
+#[derive(Default)]
+pub struct BitWriter {
+    bytes: Vec<u8>,
+    /// Bits written so far.
+    len: usize,
+}
+
+impl BitWriter {
+    /// Appends the lowest `bits` bits of `value`.
+    pub fn write(&mut self, value: u32, bits: u32) {
+        for bit in 0..bits {
+            let offset = self.len % 8;
+            if offset == 0 {
+                self.bytes.push(0);
+            }
+            if value >> bit & 1 != 0 {
+                *self.bytes.last_mut().unwrap() |= 1 << offset;
+            }
+            self.len += 1;
+        }
+    }
+
+    /// The written bits, with the last byte padded with zeroes.
+    pub fn finish(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+pub struct BitReader<'a> {
+    bytes: &'a [u8],
+    /// Bits read so far.
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    /// Reads a value written with the same number of bits, `None` past the end.
+    pub fn read(&mut self, bits: u32) -> Option<u32> {
+        let mut value = 0;
+        for bit in 0..bits {
+            let byte = self.bytes.get(self.pos / 8)?;
+            if byte >> (self.pos % 8) & 1 != 0 {
+                value |= 1 << bit;
+            }
+            self.pos += 1;
+        }
+        Some(value)
+    }
+}
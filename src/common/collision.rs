@@ -0,0 +1,102 @@
+use bevy::prelude::*;
+
+use super::player::PLAYER_SIZE;
+use super::tile::{TileRegistry, Tiles, TILE_SIZE};
+
+/// Longest distance moved in one collision step, so fast movement can't skip over a tile.
+const MAX_STEP: f32 = TILE_SIZE / 2.0;
+/// Upper bound on collision steps, so a bogus position can't stall the simulation.
+const MAX_STEPS: u32 = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+impl Aabb {
+    pub fn from_center(center: Vec2, size: Vec2) -> Self {
+        Self {
+            min: center - size / 2.0,
+            max: center + size / 2.0,
+        }
+    }
+
+    pub fn player(pos: Vec2) -> Self {
+        Self::from_center(pos, Vec2::splat(PLAYER_SIZE))
+    }
+
+    pub fn tile(pos: IVec2) -> Self {
+        Self::from_center(pos.as_vec2() * TILE_SIZE, Vec2::splat(TILE_SIZE))
+    }
+
+    /// Whether the boxes overlap, merely touching doesn't count.
+    pub fn overlaps(&self, other: &Aabb) -> bool {
+        self.min.x < other.max.x
+            && other.min.x < self.max.x
+            && self.min.y < other.max.y
+            && other.min.y < self.max.y
+    }
+
+    pub fn contains(&self, point: Vec2) -> bool {
+        self.min.cmple(point).all() && point.cmple(self.max).all()
+    }
+
+    /// Grid positions of every tile this box could overlap.
+    pub fn grid_cells(&self) -> impl Iterator<Item = IVec2> {
+        let min = (self.min / TILE_SIZE).round().as_ivec2();
+        let max = (self.max / TILE_SIZE).round().as_ivec2();
+        (min.y..=max.y).flat_map(move |y| (min.x..=max.x).map(move |x| IVec2::new(x, y)))
+    }
+}
+
+pub fn is_solid(tiles: &Tiles, registry: &TileRegistry, pos: IVec2) -> bool {
+    matches!(tiles.get(&pos), Some(&(_, kind)) if registry.get(kind).is_some_and(|def| def.solid))
+}
+
+/// Moves a player by `delta`, stopping at solid tiles and sliding along them.
+///
+/// Tiles the player already overlaps don't block, so a block placed on top of a player
+/// doesn't trap them. Moves longer than [`MAX_STEPS`] steps stop short rather than taking steps
+/// long enough to skip over a tile.
+pub fn move_and_slide(tiles: &Tiles, registry: &TileRegistry, pos: Vec2, delta: Vec2) -> Vec2 {
+    if !delta.is_finite() {
+        return pos;
+    }
+    let steps = (delta.abs().max_element() / MAX_STEP).ceil().max(1.0);
+    let step = delta / steps;
+    let mut pos = pos;
+    for _ in 0..(steps as u32).min(MAX_STEPS) {
+        pos.x = move_axis(tiles, registry, pos, step.x, 0);
+        pos.y = move_axis(tiles, registry, pos, step.y, 1);
+    }
+    pos
+}
+
+fn move_axis(tiles: &Tiles, registry: &TileRegistry, pos: Vec2, delta: f32, axis: usize) -> f32 {
+    if delta == 0.0 {
+        return pos[axis];
+    }
+
+    let start = Aabb::player(pos);
+    let mut moved = pos;
+    moved[axis] += delta;
+    let end = Aabb::player(moved);
+
+    let mut result = moved[axis];
+    for cell in end.grid_cells() {
+        if !is_solid(tiles, registry, cell) {
+            continue;
+        }
+        let tile = Aabb::tile(cell);
+        if !end.overlaps(&tile) || start.overlaps(&tile) {
+            continue;
+        }
+        if delta > 0.0 {
+            result = result.min(tile.min[axis] - PLAYER_SIZE / 2.0);
+        } else {
+            result = result.max(tile.max[axis] + PLAYER_SIZE / 2.0);
+        }
+    }
+    result
+}
@@ -0,0 +1,67 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::inventory::Inventory;
+
+pub const RECIPES_PATH: &str = "assets/recipes.ron";
+
+/// Index into the [`RecipeBook`].
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct RecipeId(pub u16);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Recipe {
+    pub inputs: BTreeMap<String, u32>,
+    pub outputs: BTreeMap<String, u32>,
+}
+
+impl Recipe {
+    pub fn can_afford(&self, inventory: &Inventory) -> bool {
+        self.inputs
+            .iter()
+            .all(|(item, &amount)| inventory.count(item) >= amount)
+    }
+
+    /// Swaps the inputs for the outputs, or does nothing if the inventory can't afford it.
+    pub fn craft(&self, inventory: &mut Inventory) -> bool {
+        if !self.can_afford(inventory) {
+            return false;
+        }
+        for (item, &amount) in &self.inputs {
+            inventory.remove(item, amount);
+        }
+        for (item, &amount) in &self.outputs {
+            inventory.add(item, amount);
+        }
+        true
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RecipeBook(Vec<Recipe>);
+
+impl RecipeBook {
+    pub fn load() -> Self {
+        let data = std::fs::read_to_string(RECIPES_PATH)
+            .unwrap_or_else(|e| panic!("Could not read {RECIPES_PATH}: {e}"));
+        Self::from_ron(&data).unwrap_or_else(|e| panic!("Invalid {RECIPES_PATH}: {e}"))
+    }
+
+    pub fn from_ron(data: &str) -> Result<Self, ron::Error> {
+        let recipes: Vec<Recipe> = ron::from_str(data)?;
+        assert!(recipes.len() <= u16::MAX as usize, "Too many recipes");
+        Ok(Self(recipes))
+    }
+
+    pub fn get(&self, id: RecipeId) -> Option<&Recipe> {
+        self.0.get(id.0 as usize)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (RecipeId, &Recipe)> {
+        self.0
+            .iter()
+            .enumerate()
+            .map(|(i, recipe)| (RecipeId(i as u16), recipe))
+    }
+}
@@ -0,0 +1,32 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Item stacks owned by a player, keyed by item name.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deref, DerefMut, Serialize, Deserialize)]
+pub struct Inventory(pub BTreeMap<String, u32>);
+
+impl Inventory {
+    pub fn count(&self, item: &str) -> u32 {
+        self.get(item).copied().unwrap_or(0)
+    }
+
+    pub fn add(&mut self, item: &str, amount: u32) {
+        *self.entry(item.to_string()).or_insert(0) += amount;
+    }
+
+    /// Takes `amount` of `item` out of the inventory, or nothing if there isn't enough.
+    pub fn remove(&mut self, item: &str, amount: u32) -> bool {
+        match self.get_mut(item) {
+            Some(count) if *count >= amount => {
+                *count -= amount;
+                if *count == 0 {
+                    self.0.remove(item);
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+}
@@ -1,19 +1,38 @@
 use std::collections::HashMap;
 
 use bevy::prelude::*;
-use bevy_renet::renet::{RenetClient, RenetServer};
-use serde::{Deserialize, Serialize};
-
-use super::player::{PlayerLocation, PlayerSyncData};
+use serde::de::{self, Visitor};
+use serde::{forward_to_deserialize_any, Deserialize, Deserializer, Serialize, Serializer};
+
+use super::crafting::RecipeId;
+use super::inventory::Inventory;
+use super::physics::{MoveInput, PlatformerBody};
+use super::player::{MovementBatch, PlayerLocation, PlayerSyncData};
+use super::rules::GameRules;
+use super::team::TeamId;
 use super::tile::TileKind;
+use super::transfer::Chunk;
+use super::transport::{NetClient, NetServer};
 
-pub const PROTOCOL_ID: u64 = 7;
+pub const PROTOCOL_ID: u64 = 15;
 
-#[derive(
-    Component, Debug, Deref, DerefMut, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash,
-)]
+#[derive(Component, Debug, Deref, DerefMut, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct NetworkId(pub Entity);
 
+// A bare `Entity` only serialises its index, which would make a tile spawned into a recycled
+// entity indistinguishable from the one that was there before.
+impl Serialize for NetworkId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.to_bits().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for NetworkId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        u64::deserialize(deserializer).map(|bits| NetworkId(Entity::from_bits(bits)))
+    }
+}
+
 #[derive(Debug, Deref, DerefMut, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
 pub struct NetworkIds(HashMap<NetworkId, Entity>);
 
@@ -31,11 +50,29 @@ pub enum NetworkSpawnCommand {
 #[derive(Debug, Serialize, Deserialize)]
 pub enum ClientReliable {
     Event(NetworkEvent),
+    Craft(RecipeId),
+    /// Asks to switch teams, the server may refuse to keep teams balanced.
+    ChangeTeam(TeamId),
+    Attack(u64),
+    /// Refuses a transfer, see [`Received::Refused`](super::transfer::Received::Refused).
+    CancelTransfer(u32),
+    /// Asks for a copy of the player's save.
+    DownloadSave,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub enum ClientUnreliable {
     PlayerMovement(PlayerLocation),
+    /// Damage dealt to a tile since the last message while the mine button is held.
+    MineBlock(NetworkId, f32),
+    /// Platformer inputs for the ticks up to and including `tick`, oldest first. Recent
+    /// inputs are repeated in every message so a lost packet doesn't lose a tick.
+    PlatformerInput {
+        tick: u32,
+        inputs: Vec<MoveInput>,
+    },
+    /// Chunks of a transfer received since the last acknowledgement.
+    TransferAck(u32, Vec<u32>),
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -44,17 +81,33 @@ pub enum ServerReliable {
     PlayerLeft(u64),
     Event(NetworkEvent),
     Spawn(NetworkId, NetworkSpawnCommand),
+    /// The full inventory of the receiving player.
+    InventoryChanged(Inventory),
+    PlayerDied(u64),
+    PlayerRespawned(u64, PlayerLocation),
+    PlayerTeamChanged(u64, TeamId, Color),
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub enum ServerUnreliable {
-    PlayerMoved(u64, PlayerLocation),
-}
-
+    /// Every other player that moved this tick, sent to each client once per tick. A client's
+    /// own player is left out, its position is predicted.
+    PlayersMoved(MovementBatch),
+    /// Where the receiving player really is, when it moved somewhere it can't be.
+    PositionCorrected(PlayerLocation),
+    /// Mining progress of a tile, from 0 (intact) to 1 (broken).
+    BlockDamaged(NetworkId, f32),
+    /// The receiving player's platformer state after simulating up to the given tick. The
+    /// position isn't quantised, as prediction replays inputs on top of it.
+    PlatformerState(u32, Vec2, PlatformerBody),
+    PlayerHealth(u64, f32),
+    TransferChunk(Chunk),
+}
+
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug, Serialize, Deserialize)]
 pub enum ServerBlocking {
-    SyncPlayers(HashMap<u64, PlayerSyncData>),
-    SyncWorld(HashMap<IVec2, (NetworkId, TileKind)>),
+    SyncRules(GameRules),
 }
 
 pub trait SendOverRenet {
@@ -70,7 +123,7 @@ pub trait RenetClientExt {
     }
 }
 
-impl RenetClientExt for RenetClient {
+impl RenetClientExt for NetClient {
     fn send<Msg: SendOverRenet>(&mut self, msg: Msg) {
         self.send_message(Msg::CHANNEL_ID, msg.prepare());
     }
@@ -86,7 +139,7 @@ pub trait RenetServerExt {
     }
 }
 
-impl RenetServerExt for RenetServer {
+impl RenetServerExt for NetServer {
     fn send_to<Msg: SendOverRenet>(&mut self, client_id: u64, msg: Msg) {
         self.send_message(client_id, Msg::CHANNEL_ID, msg.prepare());
     }
@@ -139,3 +192,54 @@ impl SendOverRenet for ServerBlocking {
         bincode::serialize(self).expect("This message is always serializable")
     }
 }
+
+/// The variant names of a message enum, in the order bincode numbers them.
+fn variant_names<'de, Msg: Deserialize<'de>>() -> &'static [&'static str] {
+    let mut names: &'static [&'static str] = &[];
+    let _ = Msg::deserialize(VariantSniffer(&mut names));
+    names
+}
+
+/// Pretends to deserialize an enum just to learn the names of its variants.
+struct VariantSniffer<'a>(&'a mut &'static [&'static str]);
+
+impl<'de, 'a> Deserializer<'de> for VariantSniffer<'a> {
+    type Error = serde::de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+        Err(de::Error::custom("only enums have variant names"))
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        variants: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        *self.0 = variants;
+        Err(de::Error::custom("only the variant names were wanted"))
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf option
+        unit unit_struct newtype_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+/// Names the message in a prepared payload, from who sent it, its channel, and the variant index
+/// bincode writes first.
+pub fn message_name(from_server: bool, channel_id: u8, message: &[u8]) -> &'static str {
+    let names = match (from_server, channel_id) {
+        (false, ClientReliable::CHANNEL_ID) => variant_names::<ClientReliable>(),
+        (false, ClientUnreliable::CHANNEL_ID) => variant_names::<ClientUnreliable>(),
+        (true, ServerReliable::CHANNEL_ID) => variant_names::<ServerReliable>(),
+        (true, ServerUnreliable::CHANNEL_ID) => variant_names::<ServerUnreliable>(),
+        (true, ServerBlocking::CHANNEL_ID) => variant_names::<ServerBlocking>(),
+        _ => &[],
+    };
+    message
+        .get(..4)
+        .and_then(|index| names.get(u32::from_le_bytes(index.try_into().unwrap()) as usize))
+        .copied()
+        .unwrap_or("unknown")
+}
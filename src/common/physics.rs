@@ -0,0 +1,86 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::collision::move_and_slide;
+use super::tile::{TileRegistry, Tiles};
+
+/// Length of one platformer simulation tick in seconds.
+pub const FIXED_DT: f32 = 1.0 / 60.0;
+const GRAVITY: f32 = 2500.0;
+const JUMP_SPEED: f32 = 1100.0;
+const MAX_FALL_SPEED: f32 = 1500.0;
+const RUN_SPEED: f32 = 400.0;
+/// Speed along each axis when flying.
+pub const FLY_SPEED: f32 = 500.0;
+/// Ticks after walking off a ledge during which a jump still counts.
+const COYOTE_TICKS: u8 = 6;
+/// Ticks a jump pressed in mid-air is remembered for, so it fires on landing.
+const JUMP_BUFFER_TICKS: u8 = 6;
+/// How far off the intended position counts as being blocked by a tile.
+const BLOCKED_EPSILON: f32 = 1e-3;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MovementMode {
+    /// Top-down movement in all four directions.
+    #[default]
+    Flying,
+    /// Side-scrolling movement with gravity and jumping.
+    Platformer,
+}
+
+/// Buttons held during one platformer tick.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MoveInput {
+    pub left: bool,
+    pub right: bool,
+    /// Whether jump was pressed since the previous tick.
+    pub jump: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct PlatformerBody {
+    pub velocity: Vec2,
+    pub grounded: bool,
+    pub coyote_ticks: u8,
+    pub jump_buffer_ticks: u8,
+}
+
+/// Advances a platformer body by one [`FIXED_DT`] tick and returns its new position.
+///
+/// Only depends on its arguments, so the client and server reach the same result from the
+/// same inputs.
+pub fn platformer_step(
+    tiles: &Tiles,
+    registry: &TileRegistry,
+    pos: Vec2,
+    body: &mut PlatformerBody,
+    input: MoveInput,
+) -> Vec2 {
+    body.jump_buffer_ticks = if input.jump {
+        JUMP_BUFFER_TICKS
+    } else {
+        body.jump_buffer_ticks.saturating_sub(1)
+    };
+    body.coyote_ticks = if body.grounded {
+        COYOTE_TICKS
+    } else {
+        body.coyote_ticks.saturating_sub(1)
+    };
+    if body.jump_buffer_ticks > 0 && body.coyote_ticks > 0 {
+        body.velocity.y = JUMP_SPEED;
+        body.jump_buffer_ticks = 0;
+        body.coyote_ticks = 0;
+    }
+
+    body.velocity.x = (input.right as i8 - input.left as i8) as f32 * RUN_SPEED;
+    body.velocity.y = (body.velocity.y - GRAVITY * FIXED_DT).max(-MAX_FALL_SPEED);
+
+    let delta = body.velocity * FIXED_DT;
+    let new_pos = move_and_slide(tiles, registry, pos, delta);
+    let blocked_y = (new_pos.y - (pos.y + delta.y)).abs() > BLOCKED_EPSILON;
+    body.grounded = blocked_y && delta.y < 0.0;
+    if blocked_y {
+        body.velocity.y = 0.0;
+    }
+    new_pos
+}
@@ -1,20 +1,222 @@
 use bevy::prelude::*;
-use serde::{Deserialize, Serialize};
+use bevy::sprite::Anchor;
+use bevy_renet::renet::NETCODE_USER_DATA_BYTES;
+use serde::de;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
+use super::bits::{bits_needed, BitReader, BitWriter};
+use super::team::TeamId;
+use super::transport::Compression;
 use crate::client::Remote;
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub const PLAYER_SIZE: f32 = 100.0;
+pub const MAX_HEALTH: f32 = 100.0;
+pub const HEALTH_BAR_SIZE: Vec2 = Vec2::new(80.0, 8.0);
+
+/// Positions travel in steps of this many pixels, far finer than anything on screen.
+pub const POSITION_STEP: f32 = 1.0 / 16.0;
+/// Bits per axis of a position, enough for half a million pixels either way of the origin.
+const POSITION_BITS: u32 = 24;
+/// Moves in one [`MovementBatch`], keeping it well inside an unreliable message.
+pub const MAX_MOVES_PER_BATCH: usize = 256;
+
+/// A player's position, sent quantised to [`POSITION_STEP`] in six bytes rather than two
+/// floats.
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct PlayerLocation(pub Vec2);
 
-#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+impl PlayerLocation {
+    fn write(&self, writer: &mut BitWriter) {
+        writer.write(quantise(self.0.x), POSITION_BITS);
+        writer.write(quantise(self.0.y), POSITION_BITS);
+    }
+
+    fn read(reader: &mut BitReader) -> Option<Self> {
+        let x = dequantise(reader.read(POSITION_BITS)?);
+        let y = dequantise(reader.read(POSITION_BITS)?);
+        Some(Self(Vec2::new(x, y)))
+    }
+}
+
+/// Offset that makes every quantised coordinate positive.
+const POSITION_OFFSET: i64 = 1 << (POSITION_BITS - 1);
+
+fn quantise(coordinate: f32) -> u32 {
+    let steps = (coordinate / POSITION_STEP).round() as i64;
+    (steps.clamp(-POSITION_OFFSET, POSITION_OFFSET - 1) + POSITION_OFFSET) as u32
+}
+
+fn dequantise(quantised: u32) -> f32 {
+    (quantised as i64 - POSITION_OFFSET) as f32 * POSITION_STEP
+}
+
+impl Serialize for PlayerLocation {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut writer = BitWriter::default();
+        self.write(&mut writer);
+        let bytes: [u8; 6] = writer
+            .finish()
+            .try_into()
+            .expect("Two axes fill exactly six bytes");
+        bytes.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for PlayerLocation {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let bytes = <[u8; 6]>::deserialize(deserializer)?;
+        Ok(Self::read(&mut BitReader::new(&bytes)).expect("Six bytes hold both axes"))
+    }
+}
+
+/// A short stand-in for a player's client id in messages sent every tick. The server hands one
+/// out to every player for as long as they are connected.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PlayerIndex(pub u16);
+
+/// Where every player that moved on a tick is now, bit-packed into a single message. Indices
+/// take as many bits as the largest of them needs. It goes over the wire as that width and the
+/// packed bytes, whose length says how many moves there are, since every move takes more bits
+/// than a byte of padding.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MovementBatch(pub Vec<(PlayerIndex, PlayerLocation)>);
+
+impl Serialize for MovementBatch {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let max_index = self.0.iter().map(|(index, _)| index.0).max().unwrap_or(0);
+        let index_bits = bits_needed(max_index as u32);
+        let mut writer = BitWriter::default();
+        for (index, location) in &self.0 {
+            writer.write(index.0 as u32, index_bits);
+            location.write(&mut writer);
+        }
+        (index_bits as u8, writer.finish()).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for MovementBatch {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (index_bits, bytes) = <(u8, Vec<u8>)>::deserialize(deserializer)?;
+        let index_bits = index_bits as u32;
+        if index_bits > u16::BITS {
+            return Err(de::Error::custom("player indices are at most 16 bits"));
+        }
+        let move_bits = (index_bits + 2 * POSITION_BITS) as usize;
+        let count = bytes.len() * 8 / move_bits;
+        if bytes.len() != (count * move_bits).div_ceil(8) {
+            return Err(de::Error::invalid_length(
+                bytes.len(),
+                &"whole moves padded to a byte",
+            ));
+        }
+
+        let mut reader = BitReader::new(&bytes);
+        let moves = (0..count)
+            .map(|_| {
+                let index = PlayerIndex(reader.read(index_bits)? as u16);
+                Some((index, PlayerLocation::read(&mut reader)?))
+            })
+            .collect::<Option<_>>()
+            .expect("The batch holds as many moves as its length says");
+        Ok(MovementBatch(moves))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PlayerSyncData {
+    /// Stands in for the player's id in [`MovementBatch`]es.
+    pub index: PlayerIndex,
+    pub name: String,
     pub pos: Vec2,
     pub color: Color,
+    pub health: f32,
+    pub team: Option<TeamId>,
+}
+
+impl Default for PlayerSyncData {
+    fn default() -> Self {
+        Self {
+            index: default(),
+            name: String::new(),
+            pos: Vec2::ZERO,
+            color: Color::default(),
+            health: MAX_HEALTH,
+            team: None,
+        }
+    }
 }
 
 #[derive(Component)]
 pub struct Player;
 
+#[derive(Component, Debug, Deref, DerefMut, Clone, Copy)]
+pub struct Health(pub f32);
+
+/// Marks a player waiting to respawn.
+#[derive(Component)]
+pub struct Dead;
+
+#[derive(Component)]
+pub struct HealthBar;
+
+#[derive(Component, Debug, Clone, Deref, DerefMut)]
+pub struct PlayerName(pub String);
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Deref, DerefMut)]
+pub struct PlayerTeam(pub Option<TeamId>);
+
+/// What a client tells the server about itself when connecting, sent as the connection's user
+/// data.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConnectInfo {
+    pub name: String,
+    /// Spectators watch the game without a player of their own.
+    pub spectator: bool,
+    /// What the client can decompress, see [`Compressed`](super::transport::Compressed).
+    pub compression: Vec<Compression>,
+}
+
+/// Bytes of user data the name can take up, the last byte holds the flags.
+const NAME_BYTES: usize = NETCODE_USER_DATA_BYTES - 1;
+const SPECTATOR_FLAG: u8 = 1;
+
+/// The bit of the flags saying a client supports `compression`.
+fn compression_bit(compression: Compression) -> u8 {
+    1 << compression.flag()
+}
+
+impl ConnectInfo {
+    /// Packs the info into user data, truncating overly long names.
+    pub fn encode(&self) -> [u8; NETCODE_USER_DATA_BYTES] {
+        let mut data = [0; NETCODE_USER_DATA_BYTES];
+        let mut len = self.name.len().min(NAME_BYTES);
+        while !self.name.is_char_boundary(len) {
+            len -= 1;
+        }
+        data[..len].copy_from_slice(&self.name.as_bytes()[..len]);
+        if self.spectator {
+            data[NAME_BYTES] |= SPECTATOR_FLAG;
+        }
+        for &compression in &self.compression {
+            data[NAME_BYTES] |= compression_bit(compression);
+        }
+        data
+    }
+
+    pub fn decode(data: &[u8; NETCODE_USER_DATA_BYTES]) -> Self {
+        let name = &data[..NAME_BYTES];
+        let len = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+        Self {
+            name: String::from_utf8_lossy(&name[..len]).into_owned(),
+            spectator: data[NAME_BYTES] & SPECTATOR_FLAG != 0,
+            compression: Compression::ALL
+                .into_iter()
+                .filter(|&compression| data[NAME_BYTES] & compression_bit(compression) != 0)
+                .collect(),
+        }
+    }
+}
+
 impl Player {
     pub fn create(commands: &mut Commands, data: PlayerSyncData, remote: bool) -> Entity {
         let mut player = commands.spawn();
@@ -22,16 +224,51 @@ impl Player {
             .insert_bundle(SpriteBundle {
                 sprite: Sprite {
                     color: data.color,
-                    custom_size: Some(Vec2::new(100.0, 100.0)),
+                    custom_size: Some(Vec2::splat(PLAYER_SIZE)),
                     ..Default::default()
                 },
                 transform: Transform {
                     translation: data.pos.extend(0.1),
                     ..Default::default()
                 },
+                visibility: Visibility {
+                    is_visible: data.health > 0.0,
+                },
                 ..Default::default()
             })
-            .insert(Player);
+            .insert(Player)
+            .insert(Health(data.health))
+            .insert(PlayerName(data.name))
+            .insert(PlayerTeam(data.team))
+            .with_children(|commands| {
+                commands
+                    .spawn_bundle(SpriteBundle {
+                        sprite: Sprite {
+                            color: Color::MAROON,
+                            custom_size: Some(HEALTH_BAR_SIZE),
+                            ..default()
+                        },
+                        transform: Transform::from_xyz(0.0, PLAYER_SIZE / 2.0 + 10.0, 0.5),
+                        ..default()
+                    })
+                    .with_children(|commands| {
+                        commands
+                            .spawn_bundle(SpriteBundle {
+                                sprite: Sprite {
+                                    color: Color::LIME_GREEN,
+                                    custom_size: Some(HEALTH_BAR_SIZE),
+                                    anchor: Anchor::CenterLeft,
+                                    ..default()
+                                },
+                                transform: Transform::from_xyz(-HEALTH_BAR_SIZE.x / 2.0, 0.0, 0.1),
+                                ..default()
+                            })
+                            .insert(HealthBar);
+                    });
+            });
+        if data.health <= 0.0 {
+            player.insert(Dead);
+        }
 
         if remote {
             player.insert(Remote);
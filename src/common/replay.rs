@@ -0,0 +1,85 @@
+use std::fs::File;
+use std::io::{BufReader, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use super::message::PROTOCOL_ID;
+
+/// Starts every replay file, replays of another protocol can't be played back.
+#[derive(Debug, Serialize, Deserialize)]
+struct ReplayHeader {
+    protocol_id: u64,
+}
+
+/// Who a recorded server message went to.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum Recipient {
+    One(u64),
+    All,
+    AllExcept(u64),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ReplayEvent {
+    /// A client connected with this user data.
+    Connected(u64, Vec<u8>),
+    Disconnected(u64),
+    /// A message from a client to the server.
+    Received {
+        client_id: u64,
+        channel_id: u8,
+        message: Vec<u8>,
+    },
+    /// A message from the server to one or more clients.
+    Sent {
+        to: Recipient,
+        channel_id: u8,
+        message: Vec<u8>,
+    },
+}
+
+/// Something that happened on the server, and when.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayEntry {
+    /// The server tick it happened on, counted from the start of the recording.
+    pub tick: u32,
+    /// Seconds since the start of the recording.
+    pub time: f64,
+    pub event: ReplayEvent,
+}
+
+pub fn write_header(writer: &mut impl Write) -> bincode::Result<()> {
+    bincode::serialize_into(
+        writer,
+        &ReplayHeader {
+            protocol_id: PROTOCOL_ID,
+        },
+    )
+}
+
+pub fn write_entry(writer: &mut impl Write, entry: &ReplayEntry) -> bincode::Result<()> {
+    bincode::serialize_into(writer, entry)
+}
+
+/// Reads a whole replay. A replay cut short by the server stopping ends at its last complete
+/// entry.
+pub fn load_replay(path: &Path) -> Vec<ReplayEntry> {
+    let file = File::open(path)
+        .unwrap_or_else(|e| panic!("Could not read replay {}: {e}", path.display()));
+    let mut reader = BufReader::new(file);
+    let header: ReplayHeader = bincode::deserialize_from(&mut reader)
+        .unwrap_or_else(|e| panic!("Invalid replay {}: {e}", path.display()));
+    if header.protocol_id != PROTOCOL_ID {
+        panic!(
+            "Replay {} was recorded with protocol {}, this game speaks {PROTOCOL_ID}",
+            path.display(),
+            header.protocol_id
+        );
+    }
+    let mut entries = Vec::new();
+    while let Ok(entry) = bincode::deserialize_from(&mut reader) {
+        entries.push(entry);
+    }
+    entries
+}
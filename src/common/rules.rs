@@ -0,0 +1,51 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::physics::MovementMode;
+use super::team::{default_teams, TeamDef, TeamId};
+use super::tile::{TileRegistry, Tiles, TILE_SIZE};
+
+/// Gameplay settings chosen by the server that clients need to mirror.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameRules {
+    pub movement: MovementMode,
+    /// Furthest distance from a player's centre to the centre of a tile they can build or mine.
+    pub reach: f32,
+    /// Distance from a spawn tile within which nothing can be built or mined, so spawn points
+    /// can't be walled in or dug out.
+    pub spawn_protection: f32,
+    /// Teams players are split into, none means everyone plays alone.
+    pub teams: Vec<TeamDef>,
+}
+
+impl Default for GameRules {
+    fn default() -> Self {
+        Self {
+            movement: default(),
+            reach: 6.0 * TILE_SIZE,
+            spawn_protection: 2.0 * TILE_SIZE,
+            teams: default_teams(),
+        }
+    }
+}
+
+impl GameRules {
+    pub fn in_reach(&self, player_pos: Vec2, tile: IVec2) -> bool {
+        player_pos.distance(tile.as_vec2() * TILE_SIZE) <= self.reach
+    }
+
+    /// Whether `tile` is close enough to a spawn tile that it can't be built on or mined.
+    pub fn is_protected(&self, tiles: &Tiles, registry: &TileRegistry, tile: IVec2) -> bool {
+        let radius = (self.spawn_protection / TILE_SIZE) as i32;
+        (-radius..=radius)
+            .flat_map(|y| (-radius..=radius).map(move |x| IVec2::new(x, y)))
+            .filter(|offset| offset.as_vec2().length() * TILE_SIZE <= self.spawn_protection)
+            .filter_map(|offset| tiles.get(&(tile + offset)))
+            .any(|&(_, kind)| matches!(registry.get(kind), Some(def) if def.spawn))
+    }
+
+    pub fn team(&self, team: TeamId) -> Option<&TeamDef> {
+        self.teams.get(team.0 as usize)
+    }
+}
@@ -0,0 +1,25 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Index into [`GameRules::teams`](super::rules::GameRules::teams).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct TeamId(pub u8);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TeamDef {
+    pub name: String,
+    pub color: Color,
+}
+
+pub fn default_teams() -> Vec<TeamDef> {
+    vec![
+        TeamDef {
+            name: "Red".to_string(),
+            color: Color::rgb(0.85, 0.2, 0.2),
+        },
+        TeamDef {
+            name: "Blue".to_string(),
+            color: Color::rgb(0.2, 0.4, 0.9),
+        },
+    ]
+}
@@ -1,36 +1,221 @@
 use std::collections::HashMap;
+use std::path::Path;
 
 use bevy::prelude::*;
 use serde::{Deserialize, Serialize};
 
 use super::message::NetworkId;
+use super::transfer::TransferKind;
+use super::transport::{pack, unpack_at_most, Compression};
 
 pub const TILE_SIZE: f32 = 50.0;
+pub const TILE_REGISTRY_PATH: &str = "assets/tiles.ron";
+/// Drawn for tiles whose kind isn't in the registry, loud enough to be noticed.
+const UNKNOWN_TILE_COLOR: Color = Color::FUCHSIA;
+/// Where tile textures are read from, [`TileDef::texture`] is relative to it.
+const ASSET_DIR: &str = "assets";
+/// Damage per second dealt to a tile while it is being mined.
+pub const MINING_SPEED: f32 = 5.0;
+/// Seconds without being hit after which a tile's damage is forgotten.
+pub const MINING_RESET_SECONDS: f64 = 1.0;
 
-#[derive(Debug, Component, Clone, Copy, Serialize, Deserialize)]
-pub enum TileKind {
-    Stone,
-    Grass,
+/// Index into the [`TileRegistry`], kept small so it serialises compactly.
+#[derive(
+    Debug, Component, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, Ord,
+)]
+pub struct TileKind(pub u16);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TileDef {
+    pub name: String,
+    #[serde(default = "default_tile_color")]
+    pub color: Color,
+    /// Path relative to the asset folder, drawn instead of `color` when present.
+    #[serde(default)]
+    pub texture: Option<String>,
+    #[serde(default = "default_hardness")]
+    pub hardness: f32,
+    #[serde(default = "default_solid")]
+    pub solid: bool,
+    /// Name of the item dropped when the tile is broken.
+    #[serde(default)]
+    pub drop: Option<String>,
+    /// Damage per second dealt to players overlapping the tile.
+    #[serde(default)]
+    pub damage: f32,
+    /// Whether new and respawning players appear at tiles of this kind.
+    #[serde(default)]
+    pub spawn: bool,
+}
+
+fn default_tile_color() -> Color {
+    Color::WHITE
+}
+
+fn default_hardness() -> f32 {
+    1.0
+}
+
+fn default_solid() -> bool {
+    true
+}
+
+/// Every tile kind there is. Clients use the server's, which comes with the world, so a client
+/// with an outdated data file still draws every tile as the server means it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(from = "Vec<TileDef>", into = "Vec<TileDef>")]
+pub struct TileRegistry {
+    defs: Vec<TileDef>,
+    by_name: HashMap<String, TileKind>,
+}
+
+impl TileRegistry {
+    pub fn load() -> Self {
+        let data = std::fs::read_to_string(TILE_REGISTRY_PATH)
+            .unwrap_or_else(|e| panic!("Could not read {TILE_REGISTRY_PATH}: {e}"));
+        Self::from_ron(&data).unwrap_or_else(|e| panic!("Invalid {TILE_REGISTRY_PATH}: {e}"))
+    }
+
+    pub fn from_ron(data: &str) -> Result<Self, ron::Error> {
+        let defs: Vec<TileDef> = ron::from_str(data)?;
+        Ok(Self::from_defs(defs))
+    }
+
+    pub fn from_defs(defs: Vec<TileDef>) -> Self {
+        assert!(defs.len() <= u16::MAX as usize, "Too many tile kinds");
+        let by_name = defs
+            .iter()
+            .enumerate()
+            .map(|(i, def)| (def.name.clone(), TileKind(i as u16)))
+            .collect();
+        Self { defs, by_name }
+    }
+
+    pub fn get(&self, kind: TileKind) -> Option<&TileDef> {
+        self.defs.get(kind.0 as usize)
+    }
+
+    pub fn kind(&self, name: &str) -> Option<TileKind> {
+        self.by_name.get(name).copied()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (TileKind, &TileDef)> {
+        self.defs
+            .iter()
+            .enumerate()
+            .map(|(i, def)| (TileKind(i as u16), def))
+    }
+
+    /// Kinds a player can hold and place, the ones that drop an item when broken.
+    pub fn placeable(&self) -> impl Iterator<Item = (TileKind, &TileDef)> {
+        self.iter().filter(|(_, def)| def.drop.is_some())
+    }
+
+    /// Reads every texture the registry's tiles are drawn with, skipping ones that can't be read.
+    pub fn load_textures(&self) -> Vec<TileTexture> {
+        let mut paths: Vec<&String> = self
+            .defs
+            .iter()
+            .filter_map(|def| def.texture.as_ref())
+            .collect();
+        paths.sort_unstable();
+        paths.dedup();
+        paths
+            .into_iter()
+            .filter_map(|path| {
+                let file = Path::new(ASSET_DIR).join(path);
+                match std::fs::read(&file) {
+                    Ok(bytes) => Some(TileTexture {
+                        path: path.clone(),
+                        bytes,
+                    }),
+                    Err(e) => {
+                        warn!(path = %file.display(), error = %e, "Could not read tile texture");
+                        None
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
+impl From<Vec<TileDef>> for TileRegistry {
+    fn from(defs: Vec<TileDef>) -> Self {
+        Self::from_defs(defs)
+    }
+}
+
+impl From<TileRegistry> for Vec<TileDef> {
+    fn from(registry: TileRegistry) -> Self {
+        registry.defs
+    }
 }
 
 #[derive(Debug, Component, Deref, DerefMut, Clone, Copy)]
 pub struct GridPos(pub IVec2);
 
-#[derive(Debug, Deref, DerefMut, Clone, Serialize, Deserialize)]
+#[derive(Debug, Default, Deref, DerefMut, Clone, Serialize, Deserialize)]
 pub struct Tiles(pub HashMap<IVec2, (NetworkId, TileKind)>);
 
-pub fn spawn_block(commands: &mut Commands, id: NetworkId, pos: IVec2, kind: TileKind) -> Entity {
+/// What a joining client is sent: every tile, and the registry their kinds index into.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorldData {
+    pub registry: TileRegistry,
+    pub tiles: Tiles,
+}
+
+impl WorldData {
+    /// Serializes and compresses the whole world at once, which shrinks it far more than
+    /// compressing each chunk of the transfer would.
+    pub fn pack(&self, compression: Option<Compression>) -> Vec<u8> {
+        let world = bincode::serialize(self).expect("The world is always serializable");
+        pack(compression, &world)
+    }
+
+    /// The world a finished transfer carried, `None` if it is malformed or too large.
+    pub fn unpack(data: &[u8]) -> Option<Self> {
+        let world = unpack_at_most(data, TransferKind::World.max_size())?;
+        bincode::deserialize(&world).ok()
+    }
+}
+
+/// An image tiles are drawn with, sent to clients so they draw what the server's files say.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TileTexture {
+    /// Path relative to the asset folder, as in [`TileDef::texture`].
+    pub path: String,
+    pub bytes: Vec<u8>,
+}
+
+pub fn spawn_block(
+    commands: &mut Commands,
+    registry: &TileRegistry,
+    asset_server: &AssetServer,
+    id: NetworkId,
+    pos: IVec2,
+    kind: TileKind,
+) -> Entity {
+    let (color, texture) = match registry.get(kind) {
+        // The image comes from the server separately and fills in the handle when it arrives.
+        Some(TileDef {
+            texture: Some(path),
+            ..
+        }) => (Color::WHITE, asset_server.get_handle(path.as_str())),
+        Some(def) => (def.color, default()),
+        None => {
+            warn!(?kind, "Drawing a tile of an unknown kind");
+            (UNKNOWN_TILE_COLOR, default())
+        }
+    };
     commands
         .spawn()
         .insert_bundle(SpriteBundle {
             sprite: Sprite {
-                color: match kind {
-                    TileKind::Stone => Color::DARK_GRAY,
-                    TileKind::Grass => Color::GREEN,
-                },
+                color,
                 custom_size: Some(Vec2::new(TILE_SIZE, TILE_SIZE)),
                 ..default()
             },
+            texture,
             transform: Transform::from_translation(Vec3::new(
                 pos.x as f32 * TILE_SIZE,
                 pos.y as f32 * TILE_SIZE,
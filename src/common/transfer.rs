@@ -0,0 +1,286 @@
+//! Sends payloads too large for a single message, like the world, in chunks over the unreliable
+//! channel. The receiver acknowledges every chunk it gets and the sender keeps resending the
+//! rest, so a transfer survives any amount of packet loss and never holds up other messages.
+
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+/// Bytes of payload in every chunk but the last.
+pub const CHUNK_SIZE: usize = 1024;
+/// Chunks sent to one client per tick, keeping well inside renet's packet budget.
+const CHUNKS_PER_TICK: usize = 4;
+/// Ticks to wait for a chunk to be acknowledged before sending it again.
+const RESEND_TICKS: u32 = 15;
+/// Downloads a client accepts at once, so a server can't make it hold many large buffers.
+const MAX_DOWNLOADS: usize = 8;
+
+/// What a transfer carries, which decides how large it may be.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransferKind {
+    /// Every tile, sent to clients as they join.
+    World,
+    /// The images the server's tiles are drawn with, sent to clients as they join.
+    Textures,
+    /// A copy of the receiving player's save, sent when they ask for one.
+    Save,
+    /// Every player, sent to clients as they join.
+    Players,
+}
+
+impl TransferKind {
+    /// The largest payload of this kind a receiver accepts, in bytes.
+    pub fn max_size(self) -> usize {
+        match self {
+            TransferKind::World => 16 * 1024 * 1024,
+            TransferKind::Textures => 16 * 1024 * 1024,
+            TransferKind::Save => 1024 * 1024,
+            TransferKind::Players => 1024 * 1024,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TransferKind::World => "world",
+            TransferKind::Textures => "textures",
+            TransferKind::Save => "save",
+            TransferKind::Players => "players",
+        }
+    }
+}
+
+/// One piece of a transfer. Every chunk says what the whole transfer is, so the receiver can
+/// start on whichever chunk arrives first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Chunk {
+    pub transfer: u32,
+    pub kind: TransferKind,
+    /// Size of the whole payload in bytes.
+    pub size: u32,
+    pub index: u32,
+    pub bytes: Vec<u8>,
+}
+
+fn chunk_count(size: usize) -> usize {
+    size.div_ceil(CHUNK_SIZE).max(1)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ChunkState {
+    Unsent,
+    /// Sent on this tick and not acknowledged yet.
+    Sent(u32),
+    Acknowledged,
+}
+
+struct Upload {
+    transfer: u32,
+    client_id: u64,
+    kind: TransferKind,
+    data: Vec<u8>,
+    chunks: Vec<ChunkState>,
+}
+
+impl Upload {
+    fn is_done(&self) -> bool {
+        self.chunks
+            .iter()
+            .all(|&state| state == ChunkState::Acknowledged)
+    }
+}
+
+/// Transfers the server is sending.
+#[derive(Default)]
+pub struct Uploads {
+    next_transfer: u32,
+    uploads: Vec<Upload>,
+}
+
+impl Uploads {
+    /// Starts sending `data` to a client, returning the transfer's id.
+    pub fn start(&mut self, client_id: u64, kind: TransferKind, data: Vec<u8>) -> u32 {
+        let transfer = self.next_transfer;
+        self.next_transfer = self.next_transfer.wrapping_add(1);
+        self.uploads.push(Upload {
+            transfer,
+            client_id,
+            kind,
+            chunks: vec![ChunkState::Unsent; chunk_count(data.len())],
+            data,
+        });
+        transfer
+    }
+
+    /// Marks chunks the client has received, finishing the transfer once it has them all.
+    pub fn acknowledge(&mut self, client_id: u64, transfer: u32, chunks: &[u32]) {
+        if let Some(upload) = self
+            .uploads
+            .iter_mut()
+            .find(|upload| upload.client_id == client_id && upload.transfer == transfer)
+        {
+            for &index in chunks {
+                if let Some(state) = upload.chunks.get_mut(index as usize) {
+                    *state = ChunkState::Acknowledged;
+                }
+            }
+        }
+        self.uploads.retain(|upload| !upload.is_done());
+    }
+
+    /// Stops a transfer the client refused.
+    pub fn cancel(&mut self, client_id: u64, transfer: u32) {
+        self.uploads
+            .retain(|upload| upload.client_id != client_id || upload.transfer != transfer);
+    }
+
+    pub fn disconnected(&mut self, client_id: u64) {
+        self.uploads.retain(|upload| upload.client_id != client_id);
+    }
+
+    /// The chunks to send on this tick: ones never sent, then ones whose acknowledgement is
+    /// overdue, oldest transfer first.
+    pub fn due(&mut self, tick: u32) -> Vec<(u64, Chunk)> {
+        let mut budgets: HashMap<u64, usize> = HashMap::new();
+        let mut due = Vec::new();
+        for upload in &mut self.uploads {
+            let budget = budgets.entry(upload.client_id).or_insert(CHUNKS_PER_TICK);
+            for (index, state) in upload.chunks.iter_mut().enumerate() {
+                if *budget == 0 {
+                    break;
+                }
+                let overdue = match *state {
+                    ChunkState::Unsent => true,
+                    ChunkState::Sent(sent) => tick.wrapping_sub(sent) >= RESEND_TICKS,
+                    ChunkState::Acknowledged => false,
+                };
+                if !overdue {
+                    continue;
+                }
+                *state = ChunkState::Sent(tick);
+                *budget -= 1;
+                let start = index * CHUNK_SIZE;
+                let end = (start + CHUNK_SIZE).min(upload.data.len());
+                due.push((
+                    upload.client_id,
+                    Chunk {
+                        transfer: upload.transfer,
+                        kind: upload.kind,
+                        size: upload.data.len() as u32,
+                        index: index as u32,
+                        bytes: upload.data[start..end].to_vec(),
+                    },
+                ));
+            }
+        }
+        due
+    }
+}
+
+struct Download {
+    kind: TransferKind,
+    size: usize,
+    chunks: Vec<Option<Vec<u8>>>,
+    received: usize,
+}
+
+/// What became of a received chunk.
+pub enum Received {
+    /// The transfer isn't complete yet, or the chunk was a duplicate.
+    Pending,
+    /// The transfer is too large or one too many, the sender should be told to stop.
+    Refused(u32),
+    Complete(TransferKind, Vec<u8>),
+}
+
+/// Transfers a client is receiving.
+#[derive(Default)]
+pub struct Downloads {
+    downloads: HashMap<u32, Download>,
+    /// Completed transfers, whose late chunks are only acknowledged again.
+    finished: HashSet<u32>,
+    /// Refused transfers, whose chunks are dropped.
+    refused: HashSet<u32>,
+    /// Chunks received since acknowledgements were last sent, per transfer.
+    acks: HashMap<u32, Vec<u32>>,
+}
+
+impl Downloads {
+    pub fn receive(&mut self, chunk: Chunk) -> Received {
+        if self.refused.contains(&chunk.transfer) {
+            return Received::Pending;
+        }
+        if self.finished.contains(&chunk.transfer) {
+            // Our acknowledgement was lost, send it again.
+            self.acks
+                .entry(chunk.transfer)
+                .or_default()
+                .push(chunk.index);
+            return Received::Pending;
+        }
+        let size = chunk.size as usize;
+        if !self.downloads.contains_key(&chunk.transfer) {
+            if size > chunk.kind.max_size() || self.downloads.len() >= MAX_DOWNLOADS {
+                self.refused.insert(chunk.transfer);
+                return Received::Refused(chunk.transfer);
+            }
+            self.downloads.insert(
+                chunk.transfer,
+                Download {
+                    kind: chunk.kind,
+                    size,
+                    chunks: vec![None; chunk_count(size)],
+                    received: 0,
+                },
+            );
+        }
+        let download = self.downloads.get_mut(&chunk.transfer).unwrap();
+        let index = chunk.index as usize;
+        let expected_len = size.saturating_sub(index * CHUNK_SIZE).min(CHUNK_SIZE);
+        if chunk.kind != download.kind
+            || size != download.size
+            || index >= download.chunks.len()
+            || chunk.bytes.len() != expected_len
+        {
+            // Not a chunk of the transfer that was announced, don't acknowledge it.
+            return Received::Pending;
+        }
+        self.acks
+            .entry(chunk.transfer)
+            .or_default()
+            .push(chunk.index);
+        if download.chunks[index].is_none() {
+            download.chunks[index] = Some(chunk.bytes);
+            download.received += 1;
+        }
+        if download.received < download.chunks.len() {
+            return Received::Pending;
+        }
+
+        let download = self.downloads.remove(&chunk.transfer).unwrap();
+        self.finished.insert(chunk.transfer);
+        let data = download.chunks.into_iter().flatten().flatten().collect();
+        Received::Complete(download.kind, data)
+    }
+
+    /// The chunks to acknowledge, per transfer, since this was last called.
+    pub fn take_acks(&mut self) -> Vec<(u32, Vec<u32>)> {
+        self.acks.drain().collect()
+    }
+
+    /// Every unfinished download with how many of its bytes have arrived.
+    pub fn progress(&self) -> Vec<(TransferKind, usize, usize)> {
+        let mut progress: Vec<_> = self
+            .downloads
+            .iter()
+            .map(|(&transfer, download)| {
+                let received = (download.received * CHUNK_SIZE).min(download.size);
+                (transfer, download.kind, received, download.size)
+            })
+            .collect();
+        progress.sort_unstable_by_key(|&(transfer, ..)| transfer);
+        progress
+            .into_iter()
+            .map(|(_, kind, received, size)| (kind, received, size))
+            .collect()
+    }
+}
@@ -0,0 +1,224 @@
+use std::time::Duration;
+
+use bevy::app::AppExit;
+use bevy::ecs::schedule::ShouldRun;
+use bevy::prelude::*;
+use bevy_renet::renet::{NetworkInfo, RenetClient, RenetError, RenetServer, ServerEvent};
+
+pub use self::compressed::{pack, unpack_at_most, Compressed, Compression};
+pub use self::conditioner::{LinkConditioner, LinkConditions, LinkProxy};
+#[cfg(test)]
+pub use self::memory::MemoryServer;
+pub use self::metered::{Metered, NetworkStats};
+#[cfg(test)]
+pub use self::playback::Playback;
+pub use self::recorder::Recorder;
+
+mod compressed;
+mod conditioner;
+#[cfg(test)]
+mod memory;
+mod metered;
+#[cfg(test)]
+mod playback;
+mod recorder;
+
+/// The server side of whatever carries messages between the server and its clients.
+pub trait ServerTransport: Send + Sync + 'static {
+    fn update(&mut self, delta: Duration) -> Result<(), RenetError>;
+    fn send_packets(&mut self) -> Result<(), RenetError>;
+    fn get_event(&mut self) -> Option<ServerEvent>;
+    fn clients_id(&self) -> Vec<u64>;
+    fn network_info(&self, client_id: u64) -> Option<NetworkInfo>;
+    fn disconnect(&mut self, client_id: u64);
+    fn receive_message(&mut self, client_id: u64, channel_id: u8) -> Option<Vec<u8>>;
+    fn send_message(&mut self, client_id: u64, channel_id: u8, message: Vec<u8>);
+    fn broadcast_message(&mut self, channel_id: u8, message: Vec<u8>);
+    fn broadcast_message_except(&mut self, client_id: u64, channel_id: u8, message: Vec<u8>);
+}
+
+/// The client side of whatever carries messages between the server and its clients.
+pub trait ClientTransport: Send + Sync + 'static {
+    fn update(&mut self, delta: Duration) -> Result<(), RenetError>;
+    fn send_packets(&mut self) -> Result<(), RenetError>;
+    fn client_id(&self) -> u64;
+    fn is_connected(&self) -> bool;
+    fn network_info(&self) -> NetworkInfo;
+    fn disconnect(&mut self);
+    fn receive_message(&mut self, channel_id: u8) -> Option<Vec<u8>>;
+    fn send_message(&mut self, channel_id: u8, message: Vec<u8>);
+}
+
+/// The server's transport, a real [`RenetServer`] or an in-memory one in tests.
+#[derive(Deref, DerefMut)]
+pub struct NetServer(Box<dyn ServerTransport>);
+
+impl NetServer {
+    pub fn new(transport: impl ServerTransport) -> Self {
+        Self(Box::new(transport))
+    }
+}
+
+/// The client's transport, a real [`RenetClient`] or an in-memory one in tests.
+#[derive(Deref, DerefMut)]
+pub struct NetClient(Box<dyn ClientTransport>);
+
+impl NetClient {
+    pub fn new(transport: impl ClientTransport) -> Self {
+        Self(Box::new(transport))
+    }
+}
+
+impl ServerTransport for RenetServer {
+    fn update(&mut self, delta: Duration) -> Result<(), RenetError> {
+        RenetServer::update(self, delta).map_err(RenetError::IO)
+    }
+
+    fn send_packets(&mut self) -> Result<(), RenetError> {
+        RenetServer::send_packets(self).map_err(RenetError::IO)
+    }
+
+    fn get_event(&mut self) -> Option<ServerEvent> {
+        RenetServer::get_event(self)
+    }
+
+    fn clients_id(&self) -> Vec<u64> {
+        RenetServer::clients_id(self)
+    }
+
+    fn network_info(&self, client_id: u64) -> Option<NetworkInfo> {
+        RenetServer::network_info(self, client_id)
+    }
+
+    fn disconnect(&mut self, client_id: u64) {
+        RenetServer::disconnect(self, client_id)
+    }
+
+    fn receive_message(&mut self, client_id: u64, channel_id: u8) -> Option<Vec<u8>> {
+        RenetServer::receive_message(self, client_id, channel_id)
+    }
+
+    fn send_message(&mut self, client_id: u64, channel_id: u8, message: Vec<u8>) {
+        RenetServer::send_message(self, client_id, channel_id, message)
+    }
+
+    fn broadcast_message(&mut self, channel_id: u8, message: Vec<u8>) {
+        RenetServer::broadcast_message(self, channel_id, message)
+    }
+
+    fn broadcast_message_except(&mut self, client_id: u64, channel_id: u8, message: Vec<u8>) {
+        RenetServer::broadcast_message_except(self, client_id, channel_id, message)
+    }
+}
+
+impl ClientTransport for RenetClient {
+    fn update(&mut self, delta: Duration) -> Result<(), RenetError> {
+        RenetClient::update(self, delta)
+    }
+
+    fn send_packets(&mut self) -> Result<(), RenetError> {
+        RenetClient::send_packets(self)
+    }
+
+    fn client_id(&self) -> u64 {
+        RenetClient::client_id(self)
+    }
+
+    fn is_connected(&self) -> bool {
+        RenetClient::is_connected(self)
+    }
+
+    fn network_info(&self) -> NetworkInfo {
+        RenetClient::network_info(self)
+    }
+
+    fn disconnect(&mut self) {
+        RenetClient::disconnect(self)
+    }
+
+    fn receive_message(&mut self, channel_id: u8) -> Option<Vec<u8>> {
+        RenetClient::receive_message(self, channel_id)
+    }
+
+    fn send_message(&mut self, channel_id: u8, message: Vec<u8>) {
+        RenetClient::send_message(self, channel_id, message)
+    }
+}
+
+/// Drives a [`NetServer`] resource, like `bevy_renet`'s plugin does for a bare [`RenetServer`].
+pub struct ServerTransportPlugin;
+
+impl Plugin for ServerTransportPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ServerEvent>()
+            .add_event::<RenetError>()
+            .add_system_to_stage(CoreStage::PreUpdate, update_server)
+            .add_system_to_stage(CoreStage::PostUpdate, send_server_packets);
+    }
+}
+
+/// Drives a [`NetClient`] resource, like `bevy_renet`'s plugin does for a bare [`RenetClient`].
+pub struct ClientTransportPlugin;
+
+impl Plugin for ClientTransportPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<RenetError>()
+            .add_system_to_stage(CoreStage::PreUpdate, update_client)
+            .add_system_to_stage(CoreStage::PostUpdate, disconnect_on_exit)
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                send_client_packets.after(disconnect_on_exit),
+            );
+    }
+}
+
+fn update_server(
+    mut server: ResMut<NetServer>,
+    mut renet_error: EventWriter<RenetError>,
+    mut server_events: EventWriter<ServerEvent>,
+    time: Res<Time>,
+) {
+    if let Err(e) = server.update(time.delta()) {
+        renet_error.send(e);
+    }
+    while let Some(event) = server.get_event() {
+        server_events.send(event);
+    }
+}
+
+fn send_server_packets(mut server: ResMut<NetServer>, mut renet_error: EventWriter<RenetError>) {
+    if let Err(e) = server.send_packets() {
+        renet_error.send(e);
+    }
+}
+
+fn update_client(
+    mut client: ResMut<NetClient>,
+    mut renet_error: EventWriter<RenetError>,
+    time: Res<Time>,
+) {
+    if let Err(e) = client.update(time.delta()) {
+        renet_error.send(e);
+    }
+}
+
+fn send_client_packets(mut client: ResMut<NetClient>, mut renet_error: EventWriter<RenetError>) {
+    if let Err(e) = client.send_packets() {
+        renet_error.send(e);
+    }
+}
+
+/// Tells the server right away when the game is closed, rather than letting it time out.
+fn disconnect_on_exit(mut client: ResMut<NetClient>, exit: EventReader<AppExit>) {
+    if !exit.is_empty() && client.is_connected() {
+        client.disconnect();
+    }
+}
+
+pub fn run_if_client_connected(client: Res<NetClient>) -> ShouldRun {
+    if client.is_connected() {
+        ShouldRun::Yes
+    } else {
+        ShouldRun::No
+    }
+}
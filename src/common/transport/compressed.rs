@@ -0,0 +1,350 @@
+use std::collections::HashMap;
+use std::io::Read;
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_renet::renet::{NetworkInfo, RenetError, ServerEvent};
+use serde::{Deserialize, Serialize};
+
+use super::{ClientTransport, ServerTransport};
+use crate::common::message::{message_name, SendOverRenet, ServerReliable, ServerUnreliable};
+use crate::common::player::ConnectInfo;
+
+/// Messages shorter than this are sent as they are, they would hardly shrink.
+pub const COMPRESSION_THRESHOLD: usize = 128;
+/// The most a message may decompress to, so a small message can't make its receiver allocate
+/// gigabytes. Transfers have their own limits.
+const MAX_DECOMPRESSED_SIZE: usize = 1024 * 1024;
+/// zstd's own default level, most of its ratio at a fraction of the time of higher levels.
+const ZSTD_LEVEL: i32 = 3;
+/// Leading bytes of a message that stay uncompressed, bincode's variant index, so the message can
+/// be named without unpacking it.
+const HEADER_LEN: usize = 4;
+/// Flag of a message sent as it is.
+const RAW_FLAG: u8 = 0;
+/// Flag of the message that tells a client which compression the server picked for it, the
+/// next byte is the picked compression's flag.
+const CHOSEN_FLAG: u8 = u8::MAX;
+
+/// An algorithm messages can be compressed with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Compression {
+    /// Fast, for when CPU time matters more than bandwidth.
+    Lz4,
+    /// Slower than LZ4 but compresses further.
+    Zstd,
+}
+
+impl Compression {
+    pub const ALL: [Compression; 2] = [Compression::Lz4, Compression::Zstd];
+
+    /// Reads `--compression=zstd,lz4`, in order of preference, or `--compression=none`. Without
+    /// the flag every algorithm is supported.
+    pub fn from_args(args: &[String]) -> Vec<Self> {
+        match args
+            .iter()
+            .find_map(|arg| arg.strip_prefix("--compression="))
+        {
+            None => Self::ALL.to_vec(),
+            Some("none") => Vec::new(),
+            Some(value) => value
+                .split(',')
+                .map(|name| match name {
+                    "lz4" => Compression::Lz4,
+                    "zstd" => Compression::Zstd,
+                    _ => panic!("--compression takes lz4, zstd or none, not {name:?}"),
+                })
+                .collect(),
+        }
+    }
+
+    /// The byte that starts every message compressed with this algorithm.
+    pub fn flag(self) -> u8 {
+        match self {
+            Compression::Lz4 => 1,
+            Compression::Zstd => 2,
+        }
+    }
+
+    /// The first of `supported` that was `offered`, what the server compresses a client's
+    /// messages and transfers with.
+    pub fn negotiate(supported: &[Self], offered: &[Self]) -> Option<Self> {
+        supported
+            .iter()
+            .copied()
+            .find(|compression| offered.contains(compression))
+    }
+
+    fn from_flag(flag: u8) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|compression| compression.flag() == flag)
+    }
+
+    fn compress(self, message: &[u8]) -> Option<Vec<u8>> {
+        match self {
+            Compression::Lz4 => Some(lz4_flex::compress_prepend_size(message)),
+            Compression::Zstd => zstd::bulk::compress(message, ZSTD_LEVEL).ok(),
+        }
+    }
+
+    fn decompress(self, data: &[u8], max_size: usize) -> Option<Vec<u8>> {
+        match self {
+            Compression::Lz4 => {
+                // The size comes first, check it before lz4_flex allocates that much.
+                let size = u32::from_le_bytes(data.get(..4)?.try_into().ok()?);
+                if size as usize > max_size {
+                    return None;
+                }
+                lz4_flex::decompress_size_prepended(data).ok()
+            }
+            Compression::Zstd => {
+                let mut message = Vec::new();
+                zstd::stream::read::Decoder::with_buffer(data)
+                    .ok()?
+                    .take(max_size as u64 + 1)
+                    .read_to_end(&mut message)
+                    .ok()?;
+                (message.len() <= max_size).then_some(message)
+            }
+        }
+    }
+}
+
+/// Puts the flag byte in front of a message, compressing all but its header first if it is large
+/// enough and actually shrinks.
+pub fn pack(compression: Option<Compression>, message: &[u8]) -> Vec<u8> {
+    if let Some(compression) = compression.filter(|_| message.len() >= COMPRESSION_THRESHOLD) {
+        let (header, body) = message.split_at(HEADER_LEN);
+        if let Some(compressed) = compression.compress(body) {
+            if compressed.len() < body.len() {
+                return [&[compression.flag()], header, compressed.as_slice()].concat();
+            }
+        }
+    }
+    [&[RAW_FLAG], message].concat()
+}
+
+/// The message [`pack`] was given, `None` if it is malformed or decompresses to too much.
+pub fn unpack(packed: &[u8]) -> Option<Vec<u8>> {
+    unpack_at_most(packed, MAX_DECOMPRESSED_SIZE)
+}
+
+/// Like [`unpack`], for payloads allowed to be larger than a message.
+pub fn unpack_at_most(packed: &[u8], max_size: usize) -> Option<Vec<u8>> {
+    let (&flag, data) = packed.split_first()?;
+    if flag == RAW_FLAG {
+        return Some(data.to_vec());
+    }
+    let compression = Compression::from_flag(flag)?;
+    let header = data.get(..HEADER_LEN)?;
+    let body = compression.decompress(&data[HEADER_LEN..], max_size)?;
+    Some([header, body.as_slice()].concat())
+}
+
+/// Whether a message from the server goes out raw without trying to compress it. Transfer chunks
+/// do: the world is compressed as a whole before it is split, and textures are compressed images.
+fn is_precompressed(channel_id: u8, message: &[u8]) -> bool {
+    channel_id == ServerUnreliable::CHANNEL_ID
+        && message_name(true, channel_id, message) == "TransferChunk"
+}
+
+/// Names a packed message like [`message_name`] does the message it holds, without unpacking it.
+pub fn packed_message_name(from_server: bool, channel_id: u8, packed: &[u8]) -> &'static str {
+    match packed {
+        [CHOSEN_FLAG, _] => "CompressionChosen",
+        [_, message @ ..] => message_name(from_server, channel_id, message),
+        [] => "unknown",
+    }
+}
+
+/// Wraps a transport and compresses large messages on every channel. Each message starts with a
+/// flag byte saying how it was compressed, so small messages cost a single byte more and the
+/// receiver never has to guess.
+///
+/// Clients offer what they support in their [`ConnectInfo`] and the server picks the first of
+/// its own preferences they offered, then tells the client. Until then, or if nothing is picked,
+/// messages go uncompressed.
+pub struct Compressed<T> {
+    inner: T,
+    /// Algorithms this side is willing to use, in order of preference.
+    supported: Vec<Compression>,
+    /// What messages to each client are compressed with. A client only has its own, which is
+    /// also what it compresses messages to the server with.
+    chosen: HashMap<u64, Compression>,
+}
+
+impl<T> Compressed<T> {
+    pub fn new(inner: T, supported: Vec<Compression>) -> Self {
+        Self {
+            inner,
+            supported,
+            chosen: HashMap::new(),
+        }
+    }
+
+    fn pack_for(&self, client_id: u64, message: &[u8]) -> Vec<u8> {
+        pack(self.chosen.get(&client_id).copied(), message)
+    }
+}
+
+impl<T: ServerTransport> Compressed<T> {
+    /// What a message to the client is compressed with, if anything.
+    fn compression_for(
+        &self,
+        client_id: u64,
+        channel_id: u8,
+        message: &[u8],
+    ) -> Option<Compression> {
+        if is_precompressed(channel_id, message) {
+            return None;
+        }
+        self.chosen.get(&client_id).copied()
+    }
+
+    /// Sends a message to several clients, compressing it once per algorithm rather than once
+    /// per client.
+    fn send_to_all(&mut self, clients: Vec<u64>, channel_id: u8, message: &[u8]) {
+        let mut packed: HashMap<Option<Compression>, Vec<u8>> = HashMap::new();
+        for client_id in clients {
+            let compression = self.compression_for(client_id, channel_id, message);
+            let message = packed
+                .entry(compression)
+                .or_insert_with(|| pack(compression, message))
+                .clone();
+            self.inner.send_message(client_id, channel_id, message);
+        }
+    }
+
+    fn choose(&mut self, client_id: u64, info: &ConnectInfo) {
+        let compression = match Compression::negotiate(&self.supported, &info.compression) {
+            Some(compression) => compression,
+            None => return,
+        };
+        debug!(client_id, ?compression, "Compressing messages to client");
+        // Clients look for this before unpacking, so it goes out without a flag of its own.
+        self.inner.send_message(
+            client_id,
+            ServerReliable::CHANNEL_ID,
+            vec![CHOSEN_FLAG, compression.flag()],
+        );
+        self.chosen.insert(client_id, compression);
+    }
+}
+
+impl<T: ServerTransport> ServerTransport for Compressed<T> {
+    fn update(&mut self, delta: Duration) -> Result<(), RenetError> {
+        self.inner.update(delta)
+    }
+
+    fn send_packets(&mut self) -> Result<(), RenetError> {
+        self.inner.send_packets()
+    }
+
+    fn get_event(&mut self) -> Option<ServerEvent> {
+        let event = self.inner.get_event()?;
+        match &event {
+            ServerEvent::ClientConnected(client_id, user_data) => {
+                self.choose(*client_id, &ConnectInfo::decode(user_data));
+            }
+            ServerEvent::ClientDisconnected(client_id) => {
+                self.chosen.remove(client_id);
+            }
+        }
+        Some(event)
+    }
+
+    fn clients_id(&self) -> Vec<u64> {
+        self.inner.clients_id()
+    }
+
+    fn network_info(&self, client_id: u64) -> Option<NetworkInfo> {
+        self.inner.network_info(client_id)
+    }
+
+    fn disconnect(&mut self, client_id: u64) {
+        self.inner.disconnect(client_id)
+    }
+
+    fn receive_message(&mut self, client_id: u64, channel_id: u8) -> Option<Vec<u8>> {
+        loop {
+            let packed = self.inner.receive_message(client_id, channel_id)?;
+            match unpack(&packed) {
+                Some(message) => return Some(message),
+                None => warn!(
+                    client_id,
+                    channel_id, "Dropped a malformed compressed message"
+                ),
+            }
+        }
+    }
+
+    fn send_message(&mut self, client_id: u64, channel_id: u8, message: Vec<u8>) {
+        let compression = self.compression_for(client_id, channel_id, &message);
+        self.inner
+            .send_message(client_id, channel_id, pack(compression, &message))
+    }
+
+    fn broadcast_message(&mut self, channel_id: u8, message: Vec<u8>) {
+        let clients = self.inner.clients_id();
+        self.send_to_all(clients, channel_id, &message);
+    }
+
+    fn broadcast_message_except(&mut self, except_id: u64, channel_id: u8, message: Vec<u8>) {
+        let mut clients = self.inner.clients_id();
+        clients.retain(|&client_id| client_id != except_id);
+        self.send_to_all(clients, channel_id, &message);
+    }
+}
+
+impl<T: ClientTransport> ClientTransport for Compressed<T> {
+    fn update(&mut self, delta: Duration) -> Result<(), RenetError> {
+        self.inner.update(delta)
+    }
+
+    fn send_packets(&mut self) -> Result<(), RenetError> {
+        self.inner.send_packets()
+    }
+
+    fn client_id(&self) -> u64 {
+        self.inner.client_id()
+    }
+
+    fn is_connected(&self) -> bool {
+        self.inner.is_connected()
+    }
+
+    fn network_info(&self) -> NetworkInfo {
+        self.inner.network_info()
+    }
+
+    fn disconnect(&mut self) {
+        self.inner.disconnect()
+    }
+
+    fn receive_message(&mut self, channel_id: u8) -> Option<Vec<u8>> {
+        let client_id = self.inner.client_id();
+        loop {
+            let packed = self.inner.receive_message(channel_id)?;
+            if let [CHOSEN_FLAG, flag] = packed[..] {
+                // Only ever use what was offered, whatever the server says.
+                match Compression::from_flag(flag).filter(|c| self.supported.contains(c)) {
+                    Some(compression) => {
+                        self.chosen.insert(client_id, compression);
+                    }
+                    None => warn!(flag, "Server picked a compression that wasn't offered"),
+                }
+                continue;
+            }
+            match unpack(&packed) {
+                Some(message) => return Some(message),
+                None => warn!(channel_id, "Dropped a malformed compressed message"),
+            }
+        }
+    }
+
+    fn send_message(&mut self, channel_id: u8, message: Vec<u8>) {
+        let message = self.pack_for(self.inner.client_id(), &message);
+        self.inner.send_message(channel_id, message)
+    }
+}
@@ -0,0 +1,276 @@
+use std::collections::HashMap;
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bevy::prelude::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Netcode never sends a packet larger than this.
+const MAX_PACKET_BYTES: usize = 1500;
+/// How often a [`LinkProxy`] running on its own thread looks for packets.
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// How bad the simulated connection is. Every setting applies to each direction separately, so
+/// a latency of 100 ms adds 200 ms to the round trip.
+///
+/// Conditions apply to the UDP packets between renet's sockets, so renet's own acks, resends and
+/// fragmentation deal with whatever goes wrong.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LinkConditions {
+    pub latency: Duration,
+    /// The most a packet's latency randomly varies by, either way.
+    pub jitter: Duration,
+    /// Chance of losing a packet, from 0 to 1.
+    pub loss: f32,
+    /// Chance of a packet arriving twice, from 0 to 1.
+    pub duplicate: f32,
+    /// Chance of a packet being held back long enough for later ones to overtake it, from 0
+    /// to 1.
+    pub reorder: f32,
+    /// Seeds which packets are lost, duplicated or held back, `None` picks a new seed every run.
+    pub seed: Option<u64>,
+}
+
+impl LinkConditions {
+    /// Reads `--latency=MS`, `--jitter=MS`, `--loss=PERCENT`, `--duplicate=PERCENT`,
+    /// `--reorder=PERCENT` and `--link-seed=SEED`, ignoring every other argument.
+    pub fn from_args(args: &[String]) -> Self {
+        let mut conditions = Self::default();
+        for arg in args {
+            if let Some((flag, value)) = arg.split_once('=') {
+                let number = || -> f32 {
+                    value
+                        .parse()
+                        .unwrap_or_else(|_| panic!("{flag} needs a number, not {value:?}"))
+                };
+                match flag {
+                    "--latency" => conditions.latency = Duration::from_secs_f32(number() / 1000.0),
+                    "--jitter" => conditions.jitter = Duration::from_secs_f32(number() / 1000.0),
+                    "--loss" => conditions.loss = number() / 100.0,
+                    "--duplicate" => conditions.duplicate = number() / 100.0,
+                    "--reorder" => conditions.reorder = number() / 100.0,
+                    "--link-seed" => {
+                        conditions.seed = Some(
+                            value
+                                .parse()
+                                .unwrap_or_else(|_| panic!("{flag} needs a seed, not {value:?}")),
+                        )
+                    }
+                    _ => {}
+                }
+            }
+        }
+        conditions.clamp()
+    }
+
+    /// Keeps every chance between 0 and 1.
+    pub fn clamp(mut self) -> Self {
+        self.loss = self.loss.clamp(0.0, 1.0);
+        self.duplicate = self.duplicate.clamp(0.0, 1.0);
+        self.reorder = self.reorder.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn is_perfect(&self) -> bool {
+        // The seed changes nothing while there is nothing to randomise.
+        Self {
+            seed: self.seed,
+            ..default()
+        } == *self
+    }
+
+    /// The randomness these conditions are applied with, seeded by `seed` or a random seed that
+    /// is logged so the run can be repeated.
+    pub fn rng(&self) -> StdRng {
+        let seed = self.seed.unwrap_or_else(rand::random);
+        info!(seed, "Seeded the link conditioner");
+        StdRng::seed_from_u64(seed)
+    }
+
+    /// How long one trip takes, latency with a random amount of jitter.
+    fn trip(&self, rng: &mut StdRng) -> Duration {
+        let jitter = self.jitter.as_secs_f32() * rng.gen_range(-1.0..=1.0);
+        Duration::from_secs_f32((self.latency.as_secs_f32() + jitter).max(0.0))
+    }
+
+    fn happens(chance: f32, rng: &mut StdRng) -> bool {
+        rng.gen::<f32>() < chance
+    }
+}
+
+/// The conditions a [`LinkProxy`] applies, shared so they can be changed while the
+/// game runs.
+#[derive(Clone, Default)]
+pub struct LinkConditioner(Arc<Mutex<LinkConditions>>);
+
+impl LinkConditioner {
+    pub fn new(conditions: LinkConditions) -> Self {
+        Self(Arc::new(Mutex::new(conditions)))
+    }
+
+    pub fn get(&self) -> LinkConditions {
+        *self.lock()
+    }
+
+    pub fn set(&self, conditions: LinkConditions) {
+        *self.lock() = conditions.clamp();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, LinkConditions> {
+        self.0
+            .lock()
+            .expect("No one panics while holding the conditions")
+    }
+}
+
+/// Which way a packet is going through a [`LinkProxy`], and which peer it belongs to.
+#[derive(Clone, Copy)]
+enum Hop {
+    ToTarget(SocketAddr),
+    ToPeer(SocketAddr),
+}
+
+struct InFlight {
+    at: Duration,
+    hop: Hop,
+    packet: Vec<u8>,
+}
+
+/// A UDP proxy that makes the connection through it worse, to see how the game copes with a bad
+/// network without leaving localhost. Peers send to the proxy as if it were the target, and every
+/// packet either way is delayed, lost, duplicated or reordered on the way.
+pub struct LinkProxy {
+    socket: UdpSocket,
+    target: SocketAddr,
+    conditioner: LinkConditioner,
+    rng: StdRng,
+    now: Duration,
+    /// A socket of its own for every peer, so the target can tell them apart.
+    routes: HashMap<SocketAddr, UdpSocket>,
+    in_flight: Vec<InFlight>,
+}
+
+impl LinkProxy {
+    /// Listens on `addr` and forwards everything it receives to `target`.
+    pub fn bind(
+        addr: SocketAddr,
+        target: SocketAddr,
+        conditioner: LinkConditioner,
+    ) -> io::Result<Self> {
+        let socket = UdpSocket::bind(addr)?;
+        socket.set_nonblocking(true)?;
+        let rng = conditioner.get().rng();
+        Ok(Self {
+            socket,
+            target,
+            conditioner,
+            rng,
+            now: Duration::ZERO,
+            routes: HashMap::new(),
+            in_flight: Vec::new(),
+        })
+    }
+
+    /// Where peers send their packets.
+    pub fn addr(&self) -> SocketAddr {
+        self.socket
+            .local_addr()
+            .expect("The proxy's socket is bound")
+    }
+
+    /// Advances the proxy by `delta`, takes in every packet that came from either side and sends
+    /// on every packet that is due.
+    pub fn update(&mut self, delta: Duration) -> io::Result<()> {
+        self.now += delta;
+        let conditions = self.conditioner.get();
+        let mut buffer = [0; MAX_PACKET_BYTES];
+        while let Some((len, peer)) = receive(&self.socket, &mut buffer)? {
+            if !self.routes.contains_key(&peer) {
+                let route = UdpSocket::bind(SocketAddr::new(self.addr().ip(), 0))?;
+                route.set_nonblocking(true)?;
+                self.routes.insert(peer, route);
+            }
+            self.delay(&conditions, Hop::ToTarget(peer), &buffer[..len]);
+        }
+        let mut replies = Vec::new();
+        for (&peer, route) in &self.routes {
+            while let Some((len, from)) = receive(route, &mut buffer)? {
+                if from == self.target {
+                    replies.push((peer, buffer[..len].to_vec()));
+                }
+            }
+        }
+        for (peer, packet) in replies {
+            self.delay(&conditions, Hop::ToPeer(peer), &packet);
+        }
+
+        let now = self.now;
+        let (mut due, waiting) = self
+            .in_flight
+            .drain(..)
+            .partition::<Vec<_>, _>(|packet| packet.at <= now);
+        self.in_flight = waiting;
+        due.sort_by_key(|packet| packet.at);
+        for packet in due {
+            match packet.hop {
+                Hop::ToTarget(peer) => {
+                    self.routes[&peer].send_to(&packet.packet, self.target)?;
+                }
+                Hop::ToPeer(peer) => {
+                    self.socket.send_to(&packet.packet, peer)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Keeps the proxy running on a thread of its own for as long as the process lives.
+    pub fn spawn(mut self) {
+        thread::spawn(move || {
+            let mut last = Instant::now();
+            loop {
+                let now = Instant::now();
+                if let Err(error) = self.update(now - last) {
+                    warn!(%error, "The link proxy failed to forward packets");
+                }
+                last = now;
+                thread::sleep(POLL_INTERVAL);
+            }
+        });
+    }
+
+    fn delay(&mut self, conditions: &LinkConditions, hop: Hop, packet: &[u8]) {
+        if LinkConditions::happens(conditions.loss, &mut self.rng) {
+            return;
+        }
+        if LinkConditions::happens(conditions.duplicate, &mut self.rng) {
+            self.in_flight.push(InFlight {
+                at: self.now + conditions.trip(&mut self.rng),
+                hop,
+                packet: packet.to_vec(),
+            });
+        }
+        let mut at = self.now + conditions.trip(&mut self.rng);
+        if LinkConditions::happens(conditions.reorder, &mut self.rng) {
+            at += conditions.latency + conditions.jitter;
+        }
+        self.in_flight.push(InFlight {
+            at,
+            hop,
+            packet: packet.to_vec(),
+        });
+    }
+}
+
+/// The next packet waiting on the socket, if any.
+fn receive(socket: &UdpSocket, buffer: &mut [u8]) -> io::Result<Option<(usize, SocketAddr)>> {
+    match socket.recv_from(buffer) {
+        Ok(received) => Ok(Some(received)),
+        Err(error) if error.kind() == io::ErrorKind::WouldBlock => Ok(None),
+        Err(error) => Err(error),
+    }
+}
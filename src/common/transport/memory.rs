@@ -0,0 +1,188 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use bevy_renet::renet::{NetworkInfo, RenetError, ServerEvent, NETCODE_USER_DATA_BYTES};
+
+use super::{ClientTransport, ServerTransport};
+
+/// Messages in flight on one channel, in the order they were sent.
+type Queue = VecDeque<Vec<u8>>;
+
+/// Both directions of one client's connection.
+#[derive(Default)]
+struct Link {
+    to_server: HashMap<u8, Queue>,
+    to_client: HashMap<u8, Queue>,
+}
+
+#[derive(Default)]
+struct Hub {
+    links: HashMap<u64, Link>,
+    events: VecDeque<ServerEvent>,
+}
+
+fn pop(queues: &mut HashMap<u8, Queue>, channel_id: u8) -> Option<Vec<u8>> {
+    queues.get_mut(&channel_id)?.pop_front()
+}
+
+fn push(queues: &mut HashMap<u8, Queue>, channel_id: u8, message: Vec<u8>) {
+    queues.entry(channel_id).or_default().push_back(message);
+}
+
+/// A server whose clients live in the same process. Messages are never lost, reordered or
+/// delayed, so tests stepping apps frame by frame always see the same result.
+#[derive(Clone, Default)]
+pub struct MemoryServer {
+    hub: Arc<Mutex<Hub>>,
+}
+
+impl MemoryServer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn hub(&self) -> MutexGuard<'_, Hub> {
+        self.hub
+            .lock()
+            .expect("No one panics while holding the hub")
+    }
+
+    /// Connects a new client, the server sees it connect on its next update.
+    pub fn connect(
+        &self,
+        client_id: u64,
+        user_data: [u8; NETCODE_USER_DATA_BYTES],
+    ) -> MemoryClient {
+        let mut hub = self.hub();
+        hub.links.insert(client_id, Link::default());
+        hub.events
+            .push_back(ServerEvent::ClientConnected(client_id, Box::new(user_data)));
+        MemoryClient {
+            client_id,
+            hub: self.hub.clone(),
+        }
+    }
+}
+
+impl ServerTransport for MemoryServer {
+    fn update(&mut self, _delta: Duration) -> Result<(), RenetError> {
+        Ok(())
+    }
+
+    fn send_packets(&mut self) -> Result<(), RenetError> {
+        Ok(())
+    }
+
+    fn get_event(&mut self) -> Option<ServerEvent> {
+        self.hub().events.pop_front()
+    }
+
+    fn clients_id(&self) -> Vec<u64> {
+        let mut ids: Vec<u64> = self.hub().links.keys().copied().collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    fn network_info(&self, client_id: u64) -> Option<NetworkInfo> {
+        self.hub()
+            .links
+            .contains_key(&client_id)
+            .then(NetworkInfo::default)
+    }
+
+    fn disconnect(&mut self, client_id: u64) {
+        let mut hub = self.hub();
+        if hub.links.remove(&client_id).is_some() {
+            hub.events
+                .push_back(ServerEvent::ClientDisconnected(client_id));
+        }
+    }
+
+    fn receive_message(&mut self, client_id: u64, channel_id: u8) -> Option<Vec<u8>> {
+        pop(
+            &mut self.hub().links.get_mut(&client_id)?.to_server,
+            channel_id,
+        )
+    }
+
+    fn send_message(&mut self, client_id: u64, channel_id: u8, message: Vec<u8>) {
+        if let Some(link) = self.hub().links.get_mut(&client_id) {
+            push(&mut link.to_client, channel_id, message);
+        }
+    }
+
+    fn broadcast_message(&mut self, channel_id: u8, message: Vec<u8>) {
+        for link in self.hub().links.values_mut() {
+            push(&mut link.to_client, channel_id, message.clone());
+        }
+    }
+
+    fn broadcast_message_except(&mut self, client_id: u64, channel_id: u8, message: Vec<u8>) {
+        for (_, link) in self
+            .hub()
+            .links
+            .iter_mut()
+            .filter(|(&id, _)| id != client_id)
+        {
+            push(&mut link.to_client, channel_id, message.clone());
+        }
+    }
+}
+
+/// A client connected to a [`MemoryServer`].
+pub struct MemoryClient {
+    client_id: u64,
+    hub: Arc<Mutex<Hub>>,
+}
+
+impl MemoryClient {
+    fn hub(&self) -> MutexGuard<'_, Hub> {
+        self.hub
+            .lock()
+            .expect("No one panics while holding the hub")
+    }
+}
+
+impl ClientTransport for MemoryClient {
+    fn update(&mut self, _delta: Duration) -> Result<(), RenetError> {
+        Ok(())
+    }
+
+    fn send_packets(&mut self) -> Result<(), RenetError> {
+        Ok(())
+    }
+
+    fn client_id(&self) -> u64 {
+        self.client_id
+    }
+
+    fn is_connected(&self) -> bool {
+        self.hub().links.contains_key(&self.client_id)
+    }
+
+    fn network_info(&self) -> NetworkInfo {
+        NetworkInfo::default()
+    }
+
+    fn disconnect(&mut self) {
+        let mut hub = self.hub();
+        if hub.links.remove(&self.client_id).is_some() {
+            hub.events
+                .push_back(ServerEvent::ClientDisconnected(self.client_id));
+        }
+    }
+
+    fn receive_message(&mut self, channel_id: u8) -> Option<Vec<u8>> {
+        pop(
+            &mut self.hub().links.get_mut(&self.client_id)?.to_client,
+            channel_id,
+        )
+    }
+
+    fn send_message(&mut self, channel_id: u8, message: Vec<u8>) {
+        if let Some(link) = self.hub().links.get_mut(&self.client_id) {
+            push(&mut link.to_server, channel_id, message);
+        }
+    }
+}
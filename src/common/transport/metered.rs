@@ -0,0 +1,357 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use bevy_renet::renet::{NetworkInfo, RenetError, ServerEvent};
+
+use super::compressed::packed_message_name;
+use super::{ClientTransport, ServerTransport};
+
+/// How often the per second rates are recomputed.
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Messages and bytes, either in total or per second.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Traffic {
+    pub messages: u64,
+    pub bytes: u64,
+}
+
+impl Traffic {
+    fn add(&mut self, bytes: usize) {
+        self.messages += 1;
+        self.bytes += bytes as u64;
+    }
+}
+
+impl std::ops::AddAssign for Traffic {
+    fn add_assign(&mut self, other: Self) {
+        self.messages += other.messages;
+        self.bytes += other.bytes;
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Counter {
+    total: Traffic,
+    this_window: Traffic,
+    per_second: Traffic,
+}
+
+/// One direction of a connection, counted per channel and message type.
+#[derive(Debug, Clone, Default)]
+pub struct DirectionStats(BTreeMap<(u8, &'static str), Counter>);
+
+impl DirectionStats {
+    fn record(&mut self, channel_id: u8, name: &'static str, bytes: usize) {
+        self.0
+            .entry((channel_id, name))
+            .or_default()
+            .this_window
+            .add(bytes);
+    }
+
+    fn roll(&mut self, elapsed: Duration) {
+        for counter in self.0.values_mut() {
+            let window = std::mem::take(&mut counter.this_window);
+            counter.total += window;
+            counter.per_second = Traffic {
+                messages: (window.messages as f32 / elapsed.as_secs_f32()).round() as u64,
+                bytes: (window.bytes as f32 / elapsed.as_secs_f32()).round() as u64,
+            };
+        }
+    }
+
+    /// Traffic per second on each channel.
+    pub fn per_channel(&self) -> BTreeMap<u8, Traffic> {
+        let mut channels = BTreeMap::<u8, Traffic>::new();
+        for (&(channel_id, _), counter) in &self.0 {
+            *channels.entry(channel_id).or_default() += counter.per_second;
+        }
+        channels
+    }
+
+    /// Traffic per second of each message type seen so far, busiest first.
+    pub fn per_message(&self) -> Vec<(&'static str, Traffic)> {
+        let mut messages: Vec<_> = self
+            .0
+            .iter()
+            .map(|(&(_, name), counter)| (name, counter.per_second))
+            .collect();
+        messages.sort_by_key(|&(name, traffic)| (std::cmp::Reverse(traffic.bytes), name));
+        messages
+    }
+
+    /// Traffic since the connection was made.
+    pub fn total(&self) -> Traffic {
+        let mut total = Traffic::default();
+        for counter in self.0.values() {
+            total += counter.total;
+        }
+        total
+    }
+}
+
+/// What is known about one connection.
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionStats {
+    pub info: NetworkInfo,
+    pub sent: DirectionStats,
+    pub received: DirectionStats,
+    window: Duration,
+}
+
+impl ConnectionStats {
+    fn tick(&mut self, delta: Duration) {
+        self.window += delta;
+        if self.window >= RATE_WINDOW {
+            self.sent.roll(self.window);
+            self.received.roll(self.window);
+            self.window = Duration::ZERO;
+        }
+    }
+
+    /// A table of the statistics, for the overlay and the server console.
+    pub fn report(&self) -> String {
+        let mut report = format!(
+            "rtt {:.0} ms  loss {:.1} %\n",
+            self.info.rtt,
+            self.info.packet_loss * 100.0
+        );
+        let sent = self.sent.per_channel();
+        let received = self.received.per_channel();
+        let mut channels: Vec<u8> = sent.keys().chain(received.keys()).copied().collect();
+        channels.sort_unstable();
+        channels.dedup();
+        let _ = writeln!(report, "{:<22}{:<18}received", "", "sent");
+        for channel_id in channels {
+            let _ = writeln!(
+                report,
+                "{:<22}{:<18}{}",
+                format!("channel {channel_id}"),
+                rate(sent.get(&channel_id).copied().unwrap_or_default()),
+                rate(received.get(&channel_id).copied().unwrap_or_default()),
+            );
+        }
+        let _ = writeln!(
+            report,
+            "{:<22}{:<18}{}",
+            "total",
+            bytes(self.sent.total().bytes),
+            bytes(self.received.total().bytes),
+        );
+        for (arrow, direction) in [("->", &self.sent), ("<-", &self.received)] {
+            for (name, traffic) in direction.per_message() {
+                let _ = writeln!(report, "{arrow} {name:<19}{}", rate(traffic));
+            }
+        }
+        report
+    }
+}
+
+fn rate(traffic: Traffic) -> String {
+    format!("{}/s {}/s", traffic.messages, bytes(traffic.bytes))
+}
+
+fn bytes(bytes: u64) -> String {
+    match bytes {
+        0..=1023 => format!("{bytes} B"),
+        1024..=1_048_575 => format!("{:.1} KiB", bytes as f32 / 1024.0),
+        _ => format!("{:.1} MiB", bytes as f32 / 1_048_576.0),
+    }
+}
+
+/// Everything ever sent and received per channel and message type, including over connections
+/// that have since closed.
+#[derive(Debug, Clone, Default)]
+pub struct TrafficTotals {
+    pub sent: BTreeMap<(u8, &'static str), Traffic>,
+    pub received: BTreeMap<(u8, &'static str), Traffic>,
+}
+
+#[derive(Default)]
+struct Stats {
+    connections: HashMap<u64, ConnectionStats>,
+    totals: TrafficTotals,
+}
+
+/// Statistics for every connection of a [`Metered`] transport, shared so they can be shown while
+/// the game runs. A client only has the one connection to the server.
+#[derive(Clone, Default)]
+pub struct NetworkStats(Arc<Mutex<Stats>>);
+
+impl NetworkStats {
+    pub fn get(&self, client_id: u64) -> Option<ConnectionStats> {
+        self.lock().connections.get(&client_id).cloned()
+    }
+
+    /// Every connection, ordered by client id.
+    pub fn all(&self) -> BTreeMap<u64, ConnectionStats> {
+        self.lock()
+            .connections
+            .iter()
+            .map(|(&id, stats)| (id, stats.clone()))
+            .collect()
+    }
+
+    pub fn totals(&self) -> TrafficTotals {
+        self.lock().totals.clone()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Stats> {
+        self.0
+            .lock()
+            .expect("No one panics while holding the statistics")
+    }
+}
+
+/// Wraps a transport and counts what goes through it. It sits below [`Compressed`] so it counts
+/// the bytes that actually go over the wire, and names messages from the header [`pack`] leaves
+/// uncompressed.
+///
+/// [`Compressed`]: super::compressed::Compressed
+/// [`pack`]: super::compressed::pack
+pub struct Metered<T> {
+    inner: T,
+    stats: NetworkStats,
+}
+
+impl<T> Metered<T> {
+    pub fn new(inner: T, stats: NetworkStats) -> Self {
+        Self { inner, stats }
+    }
+
+    fn record_sent(&self, client_id: u64, from_server: bool, channel_id: u8, message: &[u8]) {
+        let name = packed_message_name(from_server, channel_id, message);
+        let mut stats = self.stats.lock();
+        let connection = stats.connections.entry(client_id).or_default();
+        connection.sent.record(channel_id, name, message.len());
+        stats
+            .totals
+            .sent
+            .entry((channel_id, name))
+            .or_default()
+            .add(message.len());
+    }
+
+    fn record_received(&self, client_id: u64, from_server: bool, channel_id: u8, message: &[u8]) {
+        let name = packed_message_name(from_server, channel_id, message);
+        let mut stats = self.stats.lock();
+        let connection = stats.connections.entry(client_id).or_default();
+        connection.received.record(channel_id, name, message.len());
+        stats
+            .totals
+            .received
+            .entry((channel_id, name))
+            .or_default()
+            .add(message.len());
+    }
+}
+
+impl<T: ServerTransport> ServerTransport for Metered<T> {
+    fn update(&mut self, delta: Duration) -> Result<(), RenetError> {
+        let result = self.inner.update(delta);
+        let mut stats = self.stats.lock();
+        for client_id in self.inner.clients_id() {
+            let connection = stats.connections.entry(client_id).or_default();
+            connection.info = self.inner.network_info(client_id).unwrap_or_default();
+            connection.tick(delta);
+        }
+        result
+    }
+
+    fn send_packets(&mut self) -> Result<(), RenetError> {
+        self.inner.send_packets()
+    }
+
+    fn get_event(&mut self) -> Option<ServerEvent> {
+        let event = self.inner.get_event()?;
+        if let ServerEvent::ClientDisconnected(client_id) = event {
+            self.stats.lock().connections.remove(&client_id);
+        }
+        Some(event)
+    }
+
+    fn clients_id(&self) -> Vec<u64> {
+        self.inner.clients_id()
+    }
+
+    fn network_info(&self, client_id: u64) -> Option<NetworkInfo> {
+        self.inner.network_info(client_id)
+    }
+
+    fn disconnect(&mut self, client_id: u64) {
+        self.inner.disconnect(client_id)
+    }
+
+    fn receive_message(&mut self, client_id: u64, channel_id: u8) -> Option<Vec<u8>> {
+        let message = self.inner.receive_message(client_id, channel_id)?;
+        self.record_received(client_id, false, channel_id, &message);
+        Some(message)
+    }
+
+    fn send_message(&mut self, client_id: u64, channel_id: u8, message: Vec<u8>) {
+        self.record_sent(client_id, true, channel_id, &message);
+        self.inner.send_message(client_id, channel_id, message)
+    }
+
+    fn broadcast_message(&mut self, channel_id: u8, message: Vec<u8>) {
+        for client_id in self.inner.clients_id() {
+            self.record_sent(client_id, true, channel_id, &message);
+        }
+        self.inner.broadcast_message(channel_id, message)
+    }
+
+    fn broadcast_message_except(&mut self, except_id: u64, channel_id: u8, message: Vec<u8>) {
+        for client_id in self.inner.clients_id() {
+            if client_id != except_id {
+                self.record_sent(client_id, true, channel_id, &message);
+            }
+        }
+        self.inner
+            .broadcast_message_except(except_id, channel_id, message)
+    }
+}
+
+impl<T: ClientTransport> ClientTransport for Metered<T> {
+    fn update(&mut self, delta: Duration) -> Result<(), RenetError> {
+        let result = self.inner.update(delta);
+        let mut stats = self.stats.lock();
+        let connection = stats.connections.entry(self.inner.client_id()).or_default();
+        connection.info = self.inner.network_info();
+        connection.tick(delta);
+        result
+    }
+
+    fn send_packets(&mut self) -> Result<(), RenetError> {
+        self.inner.send_packets()
+    }
+
+    fn client_id(&self) -> u64 {
+        self.inner.client_id()
+    }
+
+    fn is_connected(&self) -> bool {
+        self.inner.is_connected()
+    }
+
+    fn network_info(&self) -> NetworkInfo {
+        self.inner.network_info()
+    }
+
+    fn disconnect(&mut self) {
+        self.inner.disconnect()
+    }
+
+    fn receive_message(&mut self, channel_id: u8) -> Option<Vec<u8>> {
+        let message = self.inner.receive_message(channel_id)?;
+        self.record_received(self.inner.client_id(), true, channel_id, &message);
+        Some(message)
+    }
+
+    fn send_message(&mut self, channel_id: u8, message: Vec<u8>) {
+        self.record_sent(self.inner.client_id(), false, channel_id, &message);
+        self.inner.send_message(channel_id, message)
+    }
+}
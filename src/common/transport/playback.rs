@@ -0,0 +1,98 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+use bevy_renet::renet::{NetworkInfo, RenetError, ServerEvent};
+
+use super::ServerTransport;
+use crate::common::replay::{ReplayEntry, ReplayEvent};
+
+/// A server transport that plays back what clients did in a recording, each on the tick it was
+/// recorded on. What the server sends goes nowhere.
+pub struct Playback {
+    entries: VecDeque<ReplayEntry>,
+    tick: u32,
+    clients: Vec<u64>,
+    events: VecDeque<ServerEvent>,
+    messages: HashMap<(u64, u8), VecDeque<Vec<u8>>>,
+}
+
+impl Playback {
+    pub fn new(entries: Vec<ReplayEntry>) -> Self {
+        Self {
+            entries: entries.into(),
+            tick: 0,
+            clients: Vec::new(),
+            events: VecDeque::new(),
+            messages: HashMap::new(),
+        }
+    }
+}
+
+impl ServerTransport for Playback {
+    fn update(&mut self, _delta: Duration) -> Result<(), RenetError> {
+        self.tick += 1;
+        while let Some(entry) = self.entries.front() {
+            if entry.tick > self.tick {
+                break;
+            }
+            match self.entries.pop_front().unwrap().event {
+                ReplayEvent::Connected(client_id, user_data) => {
+                    if let Ok(user_data) = user_data.try_into() {
+                        self.clients.push(client_id);
+                        self.events.push_back(ServerEvent::ClientConnected(
+                            client_id,
+                            Box::new(user_data),
+                        ));
+                    }
+                }
+                ReplayEvent::Disconnected(client_id) => {
+                    self.clients.retain(|&id| id != client_id);
+                    self.events
+                        .push_back(ServerEvent::ClientDisconnected(client_id));
+                }
+                ReplayEvent::Received {
+                    client_id,
+                    channel_id,
+                    message,
+                } => self
+                    .messages
+                    .entry((client_id, channel_id))
+                    .or_default()
+                    .push_back(message),
+                ReplayEvent::Sent { .. } => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn send_packets(&mut self) -> Result<(), RenetError> {
+        Ok(())
+    }
+
+    fn get_event(&mut self) -> Option<ServerEvent> {
+        self.events.pop_front()
+    }
+
+    fn clients_id(&self) -> Vec<u64> {
+        self.clients.clone()
+    }
+
+    fn network_info(&self, _client_id: u64) -> Option<NetworkInfo> {
+        None
+    }
+
+    /// The recording has the disconnect event that followed, it is played back in its place.
+    fn disconnect(&mut self, client_id: u64) {
+        self.clients.retain(|&id| id != client_id);
+    }
+
+    fn receive_message(&mut self, client_id: u64, channel_id: u8) -> Option<Vec<u8>> {
+        self.messages.get_mut(&(client_id, channel_id))?.pop_front()
+    }
+
+    fn send_message(&mut self, _client_id: u64, _channel_id: u8, _message: Vec<u8>) {}
+
+    fn broadcast_message(&mut self, _channel_id: u8, _message: Vec<u8>) {}
+
+    fn broadcast_message_except(&mut self, _client_id: u64, _channel_id: u8, _message: Vec<u8>) {}
+}
@@ -0,0 +1,130 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use bevy::prelude::*;
+use bevy_renet::renet::{NetworkInfo, RenetError, ServerEvent};
+
+use super::ServerTransport;
+use crate::common::replay::{write_entry, write_header, Recipient, ReplayEntry, ReplayEvent};
+
+/// Wraps the server's transport and writes everything that goes through it to a replay file.
+pub struct Recorder<T> {
+    inner: T,
+    writer: BufWriter<File>,
+    tick: u32,
+    time: Duration,
+}
+
+impl<T: ServerTransport> Recorder<T> {
+    /// Starts recording into a new file in `dir`, named after the current time.
+    pub fn create(inner: T, dir: &Path) -> Self {
+        let started = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let path: PathBuf = dir.join(format!("{started}.replay"));
+        let mut writer = fs::create_dir_all(dir)
+            .and_then(|_| File::create(&path))
+            .map(BufWriter::new)
+            .unwrap_or_else(|e| panic!("Can't record a replay to {}: {e}", path.display()));
+        write_header(&mut writer)
+            .unwrap_or_else(|e| panic!("Can't record a replay to {}: {e}", path.display()));
+        info!(path = %path.display(), "Recording a replay");
+        Self {
+            inner,
+            writer,
+            tick: 0,
+            time: Duration::ZERO,
+        }
+    }
+
+    fn record(&mut self, event: ReplayEvent) {
+        let entry = ReplayEntry {
+            tick: self.tick,
+            time: self.time.as_secs_f64(),
+            event,
+        };
+        if let Err(e) = write_entry(&mut self.writer, &entry) {
+            error!(error = %e, "Could not record a replay entry");
+        }
+    }
+
+    /// Broadcasts are recorded even when no one is connected to receive them, so a replay sees
+    /// everything a spectator connected from the start would have.
+    fn record_sent(&mut self, to: Recipient, channel_id: u8, message: &[u8]) {
+        self.record(ReplayEvent::Sent {
+            to,
+            channel_id,
+            message: message.to_vec(),
+        });
+    }
+}
+
+impl<T: ServerTransport> ServerTransport for Recorder<T> {
+    fn update(&mut self, delta: Duration) -> Result<(), RenetError> {
+        self.tick += 1;
+        self.time += delta;
+        self.inner.update(delta)
+    }
+
+    fn send_packets(&mut self) -> Result<(), RenetError> {
+        // Flushed every tick, so a server that is killed loses at most one tick of its replay.
+        if let Err(e) = self.writer.flush() {
+            error!(error = %e, "Could not write the replay");
+        }
+        self.inner.send_packets()
+    }
+
+    fn get_event(&mut self) -> Option<ServerEvent> {
+        let event = self.inner.get_event()?;
+        match &event {
+            ServerEvent::ClientConnected(client_id, user_data) => {
+                self.record(ReplayEvent::Connected(*client_id, user_data.to_vec()))
+            }
+            ServerEvent::ClientDisconnected(client_id) => {
+                self.record(ReplayEvent::Disconnected(*client_id))
+            }
+        }
+        Some(event)
+    }
+
+    fn clients_id(&self) -> Vec<u64> {
+        self.inner.clients_id()
+    }
+
+    fn network_info(&self, client_id: u64) -> Option<NetworkInfo> {
+        self.inner.network_info(client_id)
+    }
+
+    fn disconnect(&mut self, client_id: u64) {
+        self.inner.disconnect(client_id)
+    }
+
+    fn receive_message(&mut self, client_id: u64, channel_id: u8) -> Option<Vec<u8>> {
+        let message = self.inner.receive_message(client_id, channel_id)?;
+        self.record(ReplayEvent::Received {
+            client_id,
+            channel_id,
+            message: message.clone(),
+        });
+        Some(message)
+    }
+
+    fn send_message(&mut self, client_id: u64, channel_id: u8, message: Vec<u8>) {
+        self.record_sent(Recipient::One(client_id), channel_id, &message);
+        self.inner.send_message(client_id, channel_id, message)
+    }
+
+    fn broadcast_message(&mut self, channel_id: u8, message: Vec<u8>) {
+        self.record_sent(Recipient::All, channel_id, &message);
+        self.inner.broadcast_message(channel_id, message)
+    }
+
+    fn broadcast_message_except(&mut self, client_id: u64, channel_id: u8, message: Vec<u8>) {
+        self.record_sent(Recipient::AllExcept(client_id), channel_id, &message);
+        self.inner
+            .broadcast_message_except(client_id, channel_id, message)
+    }
+}
@@ -0,0 +1,159 @@
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use serde_json::{Map, Value};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::{format, FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::EnvFilter;
+
+use crate::{role_name, speaker};
+
+/// What is logged when neither `--log` nor `RUST_LOG` say otherwise.
+const DEFAULT_FILTER: &str = "info,wgpu=error,naga=warn";
+const DEFAULT_LOG_DIR: &str = "logs";
+
+/// The command line flags that change logging, passed on to the processes host mode starts.
+pub const LOG_FLAGS: [&str; 4] = ["--log=", "--log-json", "--log-dir=", "--no-log-file"];
+
+/// Sets up logging for the current role:
+///
+/// - `--log=FILTER` or `RUST_LOG` picks what is logged, like `info,multiplayer_game::server=debug`.
+/// - `--log-json` writes one JSON object per line instead of text.
+/// - Everything also goes to `logs/<role>.log`, or `--log-dir=DIR`, unless `--no-log-file` is
+///   given.
+pub fn init(args: &[String]) {
+    let flag = |name: &str| {
+        args.iter()
+            .find_map(|arg| arg.strip_prefix(name)?.strip_prefix('='))
+    };
+    let filter = match flag("--log") {
+        Some(filter) => filter.to_string(),
+        None => std::env::var(EnvFilter::DEFAULT_ENV).unwrap_or_else(|_| DEFAULT_FILTER.into()),
+    };
+    let filter = EnvFilter::try_new(&filter)
+        .unwrap_or_else(|e| panic!("Invalid log filter {filter:?}: {e}"));
+    let json = args.iter().any(|arg| arg == "--log-json");
+    let file = if args.iter().any(|arg| arg == "--no-log-file") {
+        None
+    } else {
+        Some(open_log_file(Path::new(
+            flag("--log-dir").unwrap_or(DEFAULT_LOG_DIR),
+        )))
+    };
+
+    let console = tracing_subscriber::fmt::layer().event_format(Prefixed::new(json));
+    let file = file.map(|file| {
+        tracing_subscriber::fmt::layer()
+            .with_ansi(false)
+            .with_writer(Mutex::new(file))
+            .event_format(Prefixed::new(json))
+    });
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(console)
+        .with(file)
+        .init();
+}
+
+fn open_log_file(dir: &Path) -> File {
+    let path = dir.join(format!("{}.log", role_name()));
+    fs::create_dir_all(dir)
+        .and_then(|_| OpenOptions::new().create(true).append(true).open(&path))
+        .unwrap_or_else(|e| panic!("Can't open log file {}: {e}", path.display()))
+}
+
+/// Starts every line with the process's role, coloured when the output is a terminal, so the
+/// interleaved output of host mode stays readable.
+enum Prefixed {
+    Text(format::Format),
+    Json,
+}
+
+impl Prefixed {
+    fn new(json: bool) -> Self {
+        if json {
+            Prefixed::Json
+        } else {
+            Prefixed::Text(format::Format::default())
+        }
+    }
+}
+
+impl<S, N> FormatEvent<S, N> for Prefixed
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        match self {
+            Prefixed::Text(format) => {
+                if writer.has_ansi_escapes() {
+                    write!(writer, "[{}] ", speaker())?;
+                } else {
+                    write!(writer, "[{}] ", role_name())?;
+                }
+                format.format_event(ctx, writer, event)
+            }
+            Prefixed::Json => {
+                let timestamp = SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs_f64();
+                let mut fields = JsonFields::default();
+                event.record(&mut fields);
+                let metadata = event.metadata();
+                let line = serde_json::json!({
+                    "timestamp": timestamp,
+                    "role": role_name(),
+                    "level": metadata.level().as_str(),
+                    "target": metadata.target(),
+                    "fields": fields.0,
+                });
+                writeln!(writer, "{line}")
+            }
+        }
+    }
+}
+
+/// An event's fields as JSON values, numbers and booleans keep their type.
+#[derive(Default)]
+struct JsonFields(Map<String, Value>);
+
+impl Visit for JsonFields {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().into(), format!("{value:?}").into());
+    }
+}
@@ -4,12 +4,18 @@ use std::sync::atomic::{AtomicU8, Ordering};
 
 use owo_colors::OwoColorize;
 
-use self::client::client;
+use self::bot::bot;
+use self::client::{client, replay};
+use self::logging::LOG_FLAGS;
 use self::server::server;
 
+mod bot;
 mod client;
 mod common;
+mod logging;
 mod server;
+#[cfg(test)]
+mod tests;
 
 #[derive(Clone, Copy)]
 #[repr(u8)]
@@ -17,22 +23,28 @@ pub enum MultiplayerRole {
     Host = 0,
     Client = 1,
     Server = 2,
+    Bot = 3,
+    Replay = 4,
 }
 static MULTIPLAYER_ROLE: AtomicU8 = AtomicU8::new(MultiplayerRole::Host as u8);
 
-#[macro_export]
-macro_rules! log {
-    ($($tt:tt)*) => {{
-        print!("[{}]: ", $crate::speaker());
-        println!($($tt)*);
-    }};
+pub fn role_name() -> &'static str {
+    match multiplayer_role() {
+        MultiplayerRole::Host => "host",
+        MultiplayerRole::Client => "client",
+        MultiplayerRole::Server => "server",
+        MultiplayerRole::Bot => "bot",
+        MultiplayerRole::Replay => "replay",
+    }
 }
 
 pub fn speaker() -> impl Display {
     match multiplayer_role() {
-        MultiplayerRole::Host => "host".green().to_string(),
-        MultiplayerRole::Client => "client".blue().to_string(),
-        MultiplayerRole::Server => "server".yellow().to_string(),
+        MultiplayerRole::Host => role_name().green().to_string(),
+        MultiplayerRole::Client => role_name().blue().to_string(),
+        MultiplayerRole::Server => role_name().yellow().to_string(),
+        MultiplayerRole::Bot => role_name().magenta().to_string(),
+        MultiplayerRole::Replay => role_name().cyan().to_string(),
     }
 }
 
@@ -41,34 +53,48 @@ pub fn multiplayer_role() -> MultiplayerRole {
         0 => MultiplayerRole::Host,
         1 => MultiplayerRole::Client,
         2 => MultiplayerRole::Server,
+        3 => MultiplayerRole::Bot,
+        4 => MultiplayerRole::Replay,
         _ => unreachable!("Invalid value for multiplayer role"),
     }
 }
 
 fn main() {
-    match std::env::args().nth(1).as_deref() {
-        Some("server") => {
-            MULTIPLAYER_ROLE.store(MultiplayerRole::Server as u8, Ordering::Relaxed);
-            server();
-        }
-        Some("client") => {
-            MULTIPLAYER_ROLE.store(MultiplayerRole::Client as u8, Ordering::Relaxed);
-            client()
-        }
-        Some("host") | None => {
-            MULTIPLAYER_ROLE.store(MultiplayerRole::Host as u8, Ordering::Relaxed);
+    let args: Vec<String> = std::env::args().collect();
+    let role = match args.get(1).map(String::as_str) {
+        Some("server") => MultiplayerRole::Server,
+        Some("client") => MultiplayerRole::Client,
+        Some("bot") => MultiplayerRole::Bot,
+        Some("replay") => MultiplayerRole::Replay,
+        Some("host") | None => MultiplayerRole::Host,
+        _ => panic!("The first argument is nonsensical"),
+    };
+    MULTIPLAYER_ROLE.store(role as u8, Ordering::Relaxed);
+    logging::init(&args);
+
+    match role {
+        MultiplayerRole::Server => server(),
+        MultiplayerRole::Client => client(),
+        MultiplayerRole::Bot => bot(),
+        MultiplayerRole::Replay => replay(),
+        MultiplayerRole::Host => {
+            let log_flags: Vec<&String> = args
+                .iter()
+                .filter(|arg| LOG_FLAGS.iter().any(|flag| arg.starts_with(flag)))
+                .collect();
             let mut server = Command::new(std::env::args().nth(0).unwrap())
                 .arg("server")
+                .args(&log_flags)
                 .spawn()
                 .unwrap();
             let mut player2 = Command::new(std::env::args().nth(0).unwrap())
                 .arg("client")
+                .args(&log_flags)
                 .spawn()
                 .unwrap();
             client();
             player2.kill().unwrap();
             server.kill().unwrap();
         }
-        _ => panic!("The first argument is nonsensical"),
     }
 }
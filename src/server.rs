@@ -1,73 +1,333 @@
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::net::UdpSocket;
-use std::time::SystemTime;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
 
+use bevy::app::ScheduleRunnerSettings;
 use bevy::prelude::*;
 use bevy_renet::renet::{
     RenetConnectionConfig, RenetServer, ServerAuthentication, ServerConfig, ServerEvent,
 };
-use bevy_renet::RenetServerPlugin;
+use serde::Serialize;
 
+pub(crate) use self::config::ServerSettings;
+use self::console::ConsolePlugin;
+use self::health::{apply_hazards, attack, respawn_players};
+use self::metrics::MetricsPlugin;
+use self::movement::{
+    apply_platformer_inputs, move_flying, regain_movement, step_idle_platformers,
+    MAX_MOVEMENT_BUDGET,
+};
+use self::save::save_players;
+pub(crate) use self::save::PlayerSave;
+pub(crate) use self::simulation::ServerTick;
+use self::simulation::{advance_tick, ServerRng};
+#[cfg(test)]
+pub(crate) use self::snapshot::world_hash;
+use self::spawn::{find_safe_spawn, pick_spawn_point};
+use self::teams::{change_team, team_color};
+use crate::common::crafting::RecipeBook;
 use crate::common::message::{
     ClientReliable, ClientUnreliable, NetworkEvent, NetworkId, NetworkSpawnCommand, RenetServerExt,
     ServerBlocking, ServerReliable, ServerUnreliable, PROTOCOL_ID,
 };
 use crate::common::panic_on_error;
-use crate::common::player::{PlayerLocation, PlayerSyncData};
-use crate::common::tile::{GridPos, TileKind, Tiles};
-use crate::log;
+use crate::common::physics::{MovementMode, PlatformerBody, FIXED_DT};
+use crate::common::player::{
+    ConnectInfo, MovementBatch, PlayerIndex, PlayerLocation, PlayerSyncData, MAX_MOVES_PER_BATCH,
+};
+use crate::common::tile::{
+    GridPos, TileKind, TileRegistry, TileTexture, Tiles, WorldData, MINING_RESET_SECONDS,
+    MINING_SPEED,
+};
+use crate::common::transfer::{TransferKind, Uploads};
+use crate::common::transport::{
+    Compressed, Compression, LinkConditioner, LinkConditions, LinkProxy, Metered, NetServer,
+    NetworkStats, Recorder, ServerTransportPlugin,
+};
+
+mod config;
+mod console;
+mod health;
+mod metrics;
+mod movement;
+mod save;
+mod simulation;
+mod snapshot;
+mod spawn;
+mod spectator;
+mod teams;
+
+/// Longest gap between two mining messages that still counts as continuous mining.
+const MAX_MINING_INTERVAL: f64 = 0.25;
+
+#[derive(Default)]
+pub(crate) struct Lobby {
+    pub(crate) players: HashMap<u64, PlayerSyncData>,
+    profiles: HashMap<u64, Profile>,
+    /// Names of connected spectators, who have no player.
+    pub(crate) spectators: HashMap<u64, String>,
+    /// Where player saves live, `None` keeps players from being saved at all.
+    save_dir: Option<PathBuf>,
+    /// Where [`Lobby::assign_index`] looks for a free index next.
+    next_index: u16,
+}
+
+/// Server-only state of a connected player.
+#[derive(Serialize)]
+struct Profile {
+    name: String,
+    save: PlayerSave,
+    body: PlatformerBody,
+    /// Last platformer tick simulated for this player, counted by their client.
+    tick: u32,
+    /// Platformer inputs the player may still have simulated, one more every server tick.
+    input_budget: u32,
+    /// When a dead player comes back to life.
+    respawn_at: Option<f64>,
+    last_attack: f64,
+    /// How far the player may still fly, regained over time.
+    movement_budget: f32,
+    /// Whether `save` changed since it was last written.
+    #[serde(skip)]
+    dirty: bool,
+}
+
+impl Lobby {
+    /// Marks the player's save for writing and sends their client the new inventory.
+    fn inventory_changed(&mut self, server: &mut NetServer, client_id: u64) {
+        if let Some(profile) = self.profiles.get_mut(&client_id) {
+            profile.dirty = true;
+        }
+        self.send_inventory(server, client_id);
+    }
+
+    fn send_inventory(&self, server: &mut NetServer, client_id: u64) {
+        if let Some(profile) = self.profiles.get(&client_id) {
+            server.send_to(
+                client_id,
+                ServerReliable::InventoryChanged(profile.save.inventory.clone()),
+            );
+        }
+    }
+
+    /// Hands out an index no connected player holds. Indices are handed out in turn rather than
+    /// reusing the lowest free one, so a late batch for a player who left can't move whoever
+    /// joined next.
+    fn assign_index(&mut self) -> PlayerIndex {
+        loop {
+            let index = PlayerIndex(self.next_index);
+            self.next_index = self.next_index.wrapping_add(1);
+            if self.players.values().all(|player| player.index != index) {
+                return index;
+            }
+        }
+    }
+
+    fn store(&self, profile: &Profile) {
+        if let Some(dir) = &self.save_dir {
+            profile.save.store(dir, &profile.name);
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct TileDamage {
+    damage: f32,
+    last_hit: f64,
+}
 
+/// Players whose position changed this tick, sent to everyone in one batch at its end.
 #[derive(Default)]
-struct Lobby {
-    players: HashMap<u64, PlayerSyncData>,
+struct MovedPlayers(BTreeSet<u64>);
+
+#[derive(Default)]
+struct Mining {
+    tiles: HashMap<NetworkId, TileDamage>,
+    last_hit_by: HashMap<u64, f64>,
 }
 
 pub fn server() {
     let server_addr = "127.0.0.1:5000".parse().unwrap();
-    let socket = UdpSocket::bind(server_addr).unwrap();
     let current_time = SystemTime::now()
         .duration_since(SystemTime::UNIX_EPOCH)
         .unwrap();
     let connection_config = RenetConnectionConfig::default();
-    let server_config =
-        ServerConfig::new(64, PROTOCOL_ID, server_addr, ServerAuthentication::Unsecure);
+    let settings = ServerSettings::load();
+    let server_config = ServerConfig::new(
+        settings.max_players + settings.max_spectators,
+        PROTOCOL_ID,
+        server_addr,
+        ServerAuthentication::Unsecure,
+    );
+
+    let args: Vec<String> = std::env::args().skip(2).collect();
+    let conditions = LinkConditions::from_args(&args);
+    let socket = if conditions.is_perfect() {
+        UdpSocket::bind(server_addr).unwrap()
+    } else {
+        warn!(?conditions, "Simulating a bad connection");
+        // Clients reach the server through a proxy on its public address.
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let target = socket.local_addr().unwrap();
+        LinkProxy::bind(server_addr, target, LinkConditioner::new(conditions))
+            .unwrap()
+            .spawn();
+        socket
+    };
 
     let server = RenetServer::new(current_time, server_config, connection_config, socket).unwrap();
+    let stats = NetworkStats::default();
+    let server = Metered::new(server, stats.clone());
+    let server = Compressed::new(server, settings.compression.clone());
+    let server = match &settings.replay_dir {
+        Some(dir) => NetServer::new(Recorder::create(server, dir)),
+        None => NetServer::new(server),
+    };
+    let metrics_addr = settings.metrics_addr;
+    let mut app = server_app(server, settings);
+    app.insert_resource(stats).add_plugin(ConsolePlugin);
+    if let Some(addr) = metrics_addr {
+        app.add_plugin(MetricsPlugin(addr));
+    }
+    app.run();
+}
 
-    App::new()
-        .add_plugins(MinimalPlugins)
-        .add_plugin(RenetServerPlugin)
+/// Builds the server without running it, so tests can drive it over any transport.
+pub(crate) fn server_app(server: NetServer, settings: ServerSettings) -> App {
+    let registry = TileRegistry::load();
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugin(ServerTransportPlugin)
         .insert_resource(server)
-        .insert_resource(Lobby::default())
+        .insert_resource(Lobby {
+            save_dir: settings.save_dir.clone(),
+            ..default()
+        })
+        .insert_resource(ServerRng::new(settings.seed))
+        .insert_resource(settings)
+        .insert_resource(TileTextures(registry.load_textures()))
+        .insert_resource(registry)
+        .insert_resource(RecipeBook::load())
+        .init_resource::<Mining>()
+        .init_resource::<MovedPlayers>()
+        .init_resource::<ServerTick>()
+        .init_resource::<Uploads>()
+        .insert_resource(ScheduleRunnerSettings::run_loop(Duration::from_secs_f32(
+            FIXED_DT,
+        )))
+        .add_system_to_stage(CoreStage::First, advance_tick)
         .add_startup_system(create_world)
+        .add_system(regain_movement.before(receive_message_system))
         .add_system(receive_message_system)
+        .add_system(step_idle_platformers.after(receive_message_system))
+        .add_system(send_movement.after(step_idle_platformers))
         .add_system(handle_events_system)
+        .add_system(send_uploads.after(handle_events_system))
         .add_system(panic_on_error)
         .add_system(update_world)
-        .run();
+        .add_system(forget_tile_damage)
+        .add_system(apply_hazards)
+        .add_system(respawn_players)
+        .add_system(save_players.after(receive_message_system));
+    app
 }
 
 fn create_block(commands: &mut Commands, pos: IVec2, kind: TileKind) -> NetworkId {
     NetworkId(commands.spawn().insert(GridPos(pos)).insert(kind).id())
 }
 
-fn create_world(mut commands: Commands) {
+fn create_world(mut commands: Commands, registry: Res<TileRegistry>) {
+    let stone = registry.kind("stone").expect("stone is a registered tile");
+    let grass = registry.kind("grass").expect("grass is a registered tile");
+    let spawn = registry.kind("spawn").expect("spawn is a registered tile");
     let mut tiles = HashMap::new();
     for y in 0..5 {
         for x in 0..5 {
             let pos = IVec2::new(x, y);
-            let kind = if (x + y) % 2 == 0 {
-                TileKind::Stone
-            } else {
-                TileKind::Grass
-            };
+            let kind = if (x + y) % 2 == 0 { stone } else { grass };
             let id = create_block(&mut commands, pos, kind);
             tiles.insert(pos, (id, kind));
         }
     }
+    // Above the middle of the ground, with room to fall onto it.
+    let pos = IVec2::new(2, 8);
+    tiles.insert(pos, (create_block(&mut commands, pos, spawn), spawn));
     commands.insert_resource(Tiles(tiles));
 }
 
+/// Sends every client the players that moved this tick, leaving out its own player, whose
+/// position it predicts and is corrected on separately.
+fn send_movement(
+    mut server: ResMut<NetServer>,
+    lobby: Res<Lobby>,
+    mut moved: ResMut<MovedPlayers>,
+) {
+    let moves: Vec<_> = std::mem::take(&mut moved.0)
+        .into_iter()
+        .filter_map(|id| lobby.players.get(&id))
+        .map(|player| (player.index, PlayerLocation(player.pos)))
+        .collect();
+    if moves.is_empty() {
+        return;
+    }
+    for client_id in server.clients_id() {
+        let own_index = lobby.players.get(&client_id).map(|player| player.index);
+        let others: Vec<_> = moves
+            .iter()
+            .filter(|&&(index, _)| Some(index) != own_index)
+            .copied()
+            .collect();
+        for batch in others.chunks(MAX_MOVES_PER_BATCH) {
+            server.send_to(
+                client_id,
+                ServerUnreliable::PlayersMoved(MovementBatch(batch.to_vec())),
+            );
+        }
+    }
+}
+
+/// Starts sending a client every player, as many players are too much for a single message.
+fn send_players(uploads: &mut Uploads, client_id: u64, players: &HashMap<u64, PlayerSyncData>) {
+    let players = bincode::serialize(players).expect("Players are always serializable");
+    uploads.start(client_id, TransferKind::Players, players);
+}
+
+/// Images the registry's tiles are drawn with, sent to every client that joins.
+pub(crate) struct TileTextures(pub(crate) Vec<TileTexture>);
+
+/// Starts sending a client the images tiles are drawn with, then every tile, too many for a
+/// single message. The world is compressed with what the client's messages are.
+fn send_world(
+    uploads: &mut Uploads,
+    client_id: u64,
+    compression: Option<Compression>,
+    tiles: &Tiles,
+    registry: &TileRegistry,
+    textures: &TileTextures,
+) {
+    if !textures.0.is_empty() {
+        let textures = bincode::serialize(&textures.0).expect("Textures are always serializable");
+        uploads.start(client_id, TransferKind::Textures, textures);
+    }
+    let world = WorldData {
+        registry: registry.clone(),
+        tiles: tiles.clone(),
+    }
+    .pack(compression);
+    uploads.start(client_id, TransferKind::World, world);
+}
+
+fn send_uploads(
+    mut server: ResMut<NetServer>,
+    mut uploads: ResMut<Uploads>,
+    tick: Res<ServerTick>,
+) {
+    for (client_id, chunk) in uploads.due(tick.0) {
+        server.send_to(client_id, ServerUnreliable::TransferChunk(chunk));
+    }
+}
+
 fn update_world(
     mut tiles: ResMut<Tiles>,
     added_tiles: Query<(Entity, &TileKind, &GridPos), Added<TileKind>>,
@@ -77,69 +337,331 @@ fn update_world(
     }
 }
 
+fn in_reach(lobby: &Lobby, settings: &ServerSettings, client_id: u64, tile: IVec2) -> bool {
+    matches!(
+        lobby.players.get(&client_id),
+        Some(player) if settings.rules.in_reach(player.pos, tile)
+    )
+}
+
+fn forget_tile_damage(mut mining: ResMut<Mining>, tick: Res<ServerTick>) {
+    let now = tick.seconds();
+    mining
+        .tiles
+        .retain(|_, tile| now - tile.last_hit < MINING_RESET_SECONDS);
+}
+
+#[allow(clippy::too_many_arguments)]
 fn receive_message_system(
     mut commands: Commands,
-    mut server: ResMut<RenetServer>,
+    mut server: ResMut<NetServer>,
     mut lobby: ResMut<Lobby>,
     mut tiles: ResMut<Tiles>,
+    mut mining: ResMut<Mining>,
+    registry: Res<TileRegistry>,
+    recipes: Res<RecipeBook>,
+    settings: Res<ServerSettings>,
+    tick: Res<ServerTick>,
+    mut uploads: ResMut<Uploads>,
+    mut moved: ResMut<MovedPlayers>,
 ) {
-    for client_id in server.clients_id().into_iter() {
+    // Sorted so clients competing for the same tile always resolve the same way.
+    let mut clients = server.clients_id();
+    clients.sort_unstable();
+    for client_id in clients {
+        if lobby.is_spectator(client_id) {
+            // Spectators can't affect the game, only their downloads are listened to.
+            while let Some(message) = server.receive_message(client_id, 0) {
+                if let Ok(ClientReliable::CancelTransfer(transfer)) = bincode::deserialize(&message)
+                {
+                    uploads.cancel(client_id, transfer);
+                }
+            }
+            while let Some(message) = server.receive_message(client_id, 1) {
+                if let Ok(ClientUnreliable::TransferAck(transfer, chunks)) =
+                    bincode::deserialize(&message)
+                {
+                    uploads.acknowledge(client_id, transfer, &chunks);
+                }
+            }
+            continue;
+        }
         while let Some(message) = server.receive_message(client_id, 0) {
-            match bincode::deserialize(&message).unwrap() {
+            let message = match bincode::deserialize(&message) {
+                Ok(message) => message,
+                Err(_) => {
+                    warn!(client_id, channel_id = 0, "Dropped a malformed message");
+                    continue;
+                }
+            };
+            match message {
                 ClientReliable::Event(event) => match event {
                     NetworkEvent::SpawnBlock(pos, kind) => {
-                        let tile = NetworkId(commands.spawn().id());
+                        if !lobby.is_alive(client_id) {
+                            continue;
+                        }
+                        let def = match registry.get(kind) {
+                            Some(def) => def,
+                            None => {
+                                warn!(client_id, ?kind, "Client tried to spawn an unknown tile");
+                                continue;
+                            }
+                        };
+                        if tiles.contains_key(&pos) {
+                            continue;
+                        }
+                        if !in_reach(&lobby, &settings, client_id, pos) {
+                            warn!(client_id, "Client tried to build out of reach");
+                            continue;
+                        }
+                        if settings.rules.is_protected(&tiles, &registry, pos) {
+                            warn!(client_id, "Client tried to build by a spawn point");
+                            continue;
+                        }
+                        let paid = match lobby.profiles.get_mut(&client_id) {
+                            Some(profile) => profile.save.inventory.remove(&def.name, 1),
+                            None => false,
+                        };
+                        if !paid {
+                            continue;
+                        }
+                        lobby.inventory_changed(&mut server, client_id);
+
+                        let tile = create_block(&mut commands, pos, kind);
+                        tiles.insert(pos, (tile, kind));
 
                         server.broadcast(ServerReliable::Spawn(
                             tile,
                             NetworkSpawnCommand::Block(pos, kind),
                         ))
                     }
-                    NetworkEvent::BreakBlock(id) => {
-                        commands.entity(*id).despawn();
-                        tiles.retain(|_, &mut (tile_id, _)| tile_id != id);
-                        server.broadcast_event(event);
+                    NetworkEvent::BreakBlock(_) => {
+                        warn!(client_id, "Client tried to break a block without mining it");
                     }
                 },
+                ClientReliable::Craft(recipe) => {
+                    let crafted = match (recipes.get(recipe), lobby.profiles.get_mut(&client_id)) {
+                        (Some(recipe), Some(profile)) => recipe.craft(&mut profile.save.inventory),
+                        _ => false,
+                    };
+                    if crafted {
+                        lobby.inventory_changed(&mut server, client_id);
+                    } else {
+                        info!(client_id, ?recipe, "Client can't craft");
+                    }
+                }
+                ClientReliable::ChangeTeam(team) => {
+                    change_team(&mut lobby, &mut server, &settings, client_id, team);
+                }
+                ClientReliable::Attack(target) => {
+                    let now = tick.seconds();
+                    attack(&mut lobby, &mut server, &settings, now, client_id, target);
+                }
+                ClientReliable::CancelTransfer(transfer) => {
+                    warn!(client_id, transfer, "Client refused a transfer");
+                    uploads.cancel(client_id, transfer);
+                }
+                ClientReliable::DownloadSave => {
+                    if let Some(profile) = lobby.profiles.get(&client_id) {
+                        let save = profile.save.to_ron().into_bytes();
+                        uploads.start(client_id, TransferKind::Save, save);
+                    }
+                }
             }
         }
         while let Some(message) = server.receive_message(client_id, 1) {
-            match bincode::deserialize(&message).unwrap() {
-                ClientUnreliable::PlayerMovement(loc @ PlayerLocation(pos)) => {
-                    lobby.players.get_mut(&client_id).unwrap().pos = pos;
+            let message = match bincode::deserialize(&message) {
+                Ok(message) => message,
+                Err(_) => {
+                    warn!(client_id, channel_id = 1, "Dropped a malformed message");
+                    continue;
+                }
+            };
+            match message {
+                ClientUnreliable::PlayerMovement(PlayerLocation(pos)) => {
+                    if settings.rules.movement != MovementMode::Flying || !lobby.is_alive(client_id)
+                    {
+                        continue;
+                    }
+                    move_flying(
+                        &mut lobby,
+                        &mut server,
+                        &tiles,
+                        &registry,
+                        &mut moved,
+                        client_id,
+                        pos,
+                    );
+                }
+                ClientUnreliable::PlatformerInput { tick, inputs } => {
+                    if settings.rules.movement != MovementMode::Platformer {
+                        continue;
+                    }
+                    apply_platformer_inputs(
+                        &mut lobby,
+                        &mut server,
+                        &tiles,
+                        &registry,
+                        &mut moved,
+                        client_id,
+                        tick,
+                        &inputs,
+                    );
+                }
+                ClientUnreliable::TransferAck(transfer, chunks) => {
+                    uploads.acknowledge(client_id, transfer, &chunks);
+                }
+                ClientUnreliable::MineBlock(id, damage) => {
+                    if !damage.is_finite() {
+                        warn!(client_id, damage, "Client dealt impossible mining damage");
+                        continue;
+                    }
+                    if !lobby.is_alive(client_id) {
+                        continue;
+                    }
+                    let (pos, kind) = match tiles.iter().find(|(_, &(tile_id, _))| tile_id == id) {
+                        Some((&pos, &(_, kind))) => (pos, kind),
+                        None => continue,
+                    };
+                    if !in_reach(&lobby, &settings, client_id, pos)
+                        || settings.rules.is_protected(&tiles, &registry, pos)
+                    {
+                        continue;
+                    }
+                    let now = tick.seconds();
+                    let elapsed = mining
+                        .last_hit_by
+                        .insert(client_id, now)
+                        .map_or(MAX_MINING_INTERVAL, |last| {
+                            (now - last).min(MAX_MINING_INTERVAL)
+                        });
+                    let def = match registry.get(kind) {
+                        Some(def) => def,
+                        None => continue,
+                    };
+                    let tile = mining.tiles.entry(id).or_insert(TileDamage {
+                        damage: 0.0,
+                        last_hit: now,
+                    });
+                    tile.damage += damage.clamp(0.0, elapsed as f32 * MINING_SPEED);
+                    tile.last_hit = now;
 
-                    server
-                        .broadcast_except(client_id, ServerUnreliable::PlayerMoved(client_id, loc));
+                    if tile.damage >= def.hardness {
+                        mining.tiles.remove(&id);
+                        commands.entity(*id).despawn();
+                        tiles.retain(|_, &mut (tile_id, _)| tile_id != id);
+                        server.broadcast_event(NetworkEvent::BreakBlock(id));
+
+                        if let Some(drop) = &def.drop {
+                            if let Some(profile) = lobby.profiles.get_mut(&client_id) {
+                                profile.save.inventory.add(drop, 1);
+                            }
+                            lobby.inventory_changed(&mut server, client_id);
+                        }
+                    } else {
+                        let progress = tile.damage / def.hardness;
+                        server.broadcast(ServerUnreliable::BlockDamaged(id, progress));
+                    }
                 }
             }
         }
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn handle_events_system(
     mut server_events: EventReader<ServerEvent>,
-    mut server: ResMut<RenetServer>,
+    mut server: ResMut<NetServer>,
     mut lobby: ResMut<Lobby>,
+    mut mining: ResMut<Mining>,
     tiles: Res<Tiles>,
+    registry: Res<TileRegistry>,
+    textures: Res<TileTextures>,
+    settings: Res<ServerSettings>,
+    mut rng: ResMut<ServerRng>,
+    mut uploads: ResMut<Uploads>,
 ) {
     for event in server_events.iter() {
         match event {
-            ServerEvent::ClientConnected(id, _user_data) => {
+            ServerEvent::ClientConnected(id, user_data) => {
+                let info = ConnectInfo::decode(user_data);
+                let compression = Compression::negotiate(&settings.compression, &info.compression);
+                if lobby.is_full(&settings, info.spectator) {
+                    info!(client_id = id, "Client rejected, the server is full");
+                    server.disconnect(*id);
+                    continue;
+                }
+                if info.spectator {
+                    send_players(&mut uploads, *id, &lobby.players);
+                    send_world(&mut uploads, *id, compression, &tiles, &registry, &textures);
+                    server.send_to(*id, ServerBlocking::SyncRules(settings.rules.clone()));
+                    lobby.spectators.insert(*id, info.name);
+                    info!(client_id = id, "Client is spectating");
+                    continue;
+                }
+
+                let mut name = info.name;
+                if lobby.profiles.values().any(|profile| profile.name == name) {
+                    name = format!("{name}-{id}");
+                }
+                let save = match &lobby.save_dir {
+                    Some(dir) => PlayerSave::load(dir, &name).unwrap_or_default(),
+                    None => default(),
+                };
+                let pos = save
+                    .position
+                    .unwrap_or_else(|| pick_spawn_point(&tiles, &registry, &mut **rng));
+                let pos = find_safe_spawn(&tiles, &registry, pos);
+                lobby.profiles.insert(
+                    *id,
+                    Profile {
+                        name: name.clone(),
+                        save,
+                        body: default(),
+                        tick: 0,
+                        input_budget: 0,
+                        respawn_at: None,
+                        last_attack: f64::NEG_INFINITY,
+                        movement_budget: MAX_MOVEMENT_BUDGET,
+                        dirty: false,
+                    },
+                );
+
+                let team = lobby.smallest_team(&settings);
                 let player_data = PlayerSyncData {
-                    pos: Vec2::new(rand::random::<f32>() * 300.0, rand::random::<f32>() * 300.0),
-                    color: Color::rgb(rand::random(), rand::random(), rand::random()),
+                    index: lobby.assign_index(),
+                    name,
+                    pos,
+                    color: team_color(&settings, team, &mut **rng),
+                    team,
+                    ..default()
                 };
-                lobby.players.insert(*id, player_data);
+                lobby.players.insert(*id, player_data.clone());
 
-                server.send_to(*id, ServerBlocking::SyncPlayers(lobby.players.clone()));
-                server.send_to(*id, ServerBlocking::SyncWorld(tiles.0.clone()));
+                send_players(&mut uploads, *id, &lobby.players);
+                send_world(&mut uploads, *id, compression, &tiles, &registry, &textures);
+                server.send_to(*id, ServerBlocking::SyncRules(settings.rules.clone()));
                 server.broadcast_except(*id, ServerReliable::PlayerJoined(*id, player_data));
-                log!("Client {} connected", id);
+                lobby.send_inventory(&mut server, *id);
+                info!(client_id = id, "Client connected");
             }
             ServerEvent::ClientDisconnected(id) => {
-                lobby.players.remove(id);
+                uploads.disconnected(*id);
+                if lobby.spectators.remove(id).is_some() {
+                    info!(client_id = id, "Spectator disconnected");
+                    continue;
+                }
+                let player = match lobby.players.remove(id) {
+                    Some(player) => player,
+                    None => continue,
+                };
+                if let Some(mut profile) = lobby.profiles.remove(id) {
+                    profile.record_position(player.pos);
+                    lobby.store(&profile);
+                }
+                mining.last_hit_by.remove(id);
                 server.broadcast_except(*id, ServerReliable::PlayerLeft(*id));
-                log!("Client {} disconnected", id);
+                info!(client_id = id, "Client disconnected");
             }
         }
     }
@@ -0,0 +1,86 @@
+use std::io::BufRead;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Mutex;
+
+use bevy::prelude::*;
+
+use super::simulation::ServerTick;
+use super::snapshot::state_hash;
+use super::{Lobby, Mining};
+use crate::common::tile::Tiles;
+use crate::common::transport::NetworkStats;
+
+const HELP: &str = "commands: stats [name], hash, help";
+
+/// Lines typed into the server's terminal, read on a separate thread so the game never waits.
+struct ConsoleInput(Mutex<Receiver<String>>);
+
+/// Lets whoever runs the server type commands into its terminal.
+pub struct ConsolePlugin;
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut App) {
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in std::io::stdin().lock().lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        app.insert_resource(ConsoleInput(Mutex::new(receiver)))
+            .add_system(run_commands);
+    }
+}
+
+impl Lobby {
+    fn name_of(&self, client_id: u64) -> Option<&str> {
+        self.players
+            .get(&client_id)
+            .map(|player| player.name.as_str())
+            .or_else(|| self.spectators.get(&client_id).map(String::as_str))
+    }
+}
+
+fn run_commands(
+    input: Res<ConsoleInput>,
+    lobby: Res<Lobby>,
+    stats: Res<NetworkStats>,
+    tick: Res<ServerTick>,
+    tiles: Res<Tiles>,
+    mining: Res<Mining>,
+) {
+    let input = input
+        .0
+        .lock()
+        .expect("No one panics while holding the console");
+    for line in input.try_iter() {
+        let mut words = line.split_whitespace();
+        match (words.next(), words.next()) {
+            (None, _) => {}
+            (Some("stats"), name) => print_stats(&lobby, &stats, name),
+            (Some("hash"), _) => {
+                let hash = state_hash(&tick, &lobby, &tiles, &mining);
+                info!(tick = tick.0, "State hash {hash:016x}");
+            }
+            (Some("help"), _) => info!("{HELP}"),
+            (Some(command), _) => warn!("Unknown command {command:?}, {HELP}"),
+        }
+    }
+}
+
+/// Prints the network statistics of every client, or only of the one with the given name.
+fn print_stats(lobby: &Lobby, stats: &NetworkStats, name: Option<&str>) {
+    let mut printed = false;
+    for (client_id, connection) in stats.all() {
+        let client_name = lobby.name_of(client_id).unwrap_or("?");
+        if matches!(name, Some(name) if name != client_name) {
+            continue;
+        }
+        info!("{client_name} ({client_id})\n{}", connection.report());
+        printed = true;
+    }
+    if !printed {
+        info!("No one to print statistics for");
+    }
+}
@@ -0,0 +1,161 @@
+use bevy::prelude::*;
+
+use super::config::ServerSettings;
+use super::simulation::{ServerRng, ServerTick};
+use super::spawn::{find_safe_spawn, pick_spawn_point};
+use super::Lobby;
+use crate::common::collision::Aabb;
+use crate::common::message::{RenetServerExt, ServerReliable, ServerUnreliable};
+use crate::common::physics::FIXED_DT;
+use crate::common::player::{PlayerLocation, MAX_HEALTH};
+use crate::common::tile::{TileRegistry, Tiles};
+use crate::common::transport::NetServer;
+
+const ATTACK_DAMAGE: f32 = 10.0;
+const ATTACK_COOLDOWN: f64 = 0.5;
+
+impl Lobby {
+    pub fn is_alive(&self, client_id: u64) -> bool {
+        matches!(self.profiles.get(&client_id), Some(profile) if profile.respawn_at.is_none())
+    }
+
+    /// Lowers a living player's health, killing them when it runs out.
+    pub fn damage(
+        &mut self,
+        server: &mut NetServer,
+        settings: &ServerSettings,
+        now: f64,
+        client_id: u64,
+        amount: f32,
+    ) {
+        if !self.is_alive(client_id) {
+            return;
+        }
+        let player = match self.players.get_mut(&client_id) {
+            Some(player) => player,
+            None => return,
+        };
+
+        let before = player.health;
+        player.health = (player.health - amount).max(0.0);
+        if player.health.ceil() != before.ceil() {
+            server.broadcast(ServerUnreliable::PlayerHealth(client_id, player.health));
+        }
+        if player.health <= 0.0 {
+            self.profiles.get_mut(&client_id).unwrap().respawn_at =
+                Some(now + settings.respawn_seconds as f64);
+            server.broadcast(ServerReliable::PlayerDied(client_id));
+            info!(client_id, "Client died");
+        }
+    }
+}
+
+pub fn attack(
+    lobby: &mut Lobby,
+    server: &mut NetServer,
+    settings: &ServerSettings,
+    now: f64,
+    attacker: u64,
+    target: u64,
+) {
+    if !settings.pvp || attacker == target || !lobby.is_alive(attacker) {
+        return;
+    }
+    let (attacker_pos, target_pos) =
+        match (lobby.players.get(&attacker), lobby.players.get(&target)) {
+            (Some(attacker), Some(target)) => (attacker.pos, target.pos),
+            _ => return,
+        };
+    if attacker_pos.distance(target_pos) > settings.rules.reach {
+        return;
+    }
+    if !settings.friendly_fire && lobby.same_team(attacker, target) {
+        return;
+    }
+    let profile = lobby.profiles.get_mut(&attacker).unwrap();
+    if now - profile.last_attack < ATTACK_COOLDOWN {
+        return;
+    }
+    profile.last_attack = now;
+    lobby.damage(server, settings, now, target, ATTACK_DAMAGE);
+}
+
+pub fn apply_hazards(
+    mut lobby: ResMut<Lobby>,
+    mut server: ResMut<NetServer>,
+    settings: Res<ServerSettings>,
+    tiles: Res<Tiles>,
+    registry: Res<TileRegistry>,
+    tick: Res<ServerTick>,
+) {
+    let now = tick.seconds();
+    let hazards: Vec<_> = lobby
+        .players
+        .iter()
+        .map(|(&id, player)| {
+            if player.pos.y < settings.void_height {
+                return (id, MAX_HEALTH);
+            }
+            let bounds = Aabb::player(player.pos);
+            let damage = bounds
+                .grid_cells()
+                .filter(|&cell| bounds.overlaps(&Aabb::tile(cell)))
+                .filter_map(|cell| tiles.get(&cell))
+                .filter_map(|&(_, kind)| registry.get(kind))
+                .map(|def| def.damage)
+                .fold(0.0, f32::max);
+            (id, damage * FIXED_DT)
+        })
+        .filter(|&(_, damage)| damage > 0.0)
+        .collect();
+
+    for (id, damage) in hazards {
+        lobby.damage(&mut server, &settings, now, id, damage);
+    }
+}
+
+pub fn respawn_players(
+    mut lobby: ResMut<Lobby>,
+    mut server: ResMut<NetServer>,
+    tiles: Res<Tiles>,
+    registry: Res<TileRegistry>,
+    tick: Res<ServerTick>,
+    mut rng: ResMut<ServerRng>,
+) {
+    let now = tick.seconds();
+    let lobby = &mut *lobby;
+    // Sorted so players respawning together always draw the same spawn points.
+    let mut respawning: Vec<u64> = lobby
+        .profiles
+        .iter()
+        .filter(|(_, profile)| matches!(profile.respawn_at, Some(respawn_at) if respawn_at <= now))
+        .map(|(&id, _)| id)
+        .collect();
+    respawning.sort_unstable();
+    for id in respawning {
+        let profile = lobby.profiles.get_mut(&id).unwrap();
+        let player = match lobby.players.get_mut(&id) {
+            Some(player) => player,
+            None => continue,
+        };
+
+        profile.respawn_at = None;
+        profile.body = default();
+        player.health = MAX_HEALTH;
+        player.pos = find_safe_spawn(
+            &tiles,
+            &registry,
+            pick_spawn_point(&tiles, &registry, &mut **rng),
+        );
+
+        server.broadcast(ServerReliable::PlayerRespawned(
+            id,
+            PlayerLocation(player.pos),
+        ));
+        server.send_to(
+            id,
+            ServerUnreliable::PlatformerState(profile.tick, player.pos, profile.body),
+        );
+        info!(client_id = id, "Client respawned");
+    }
+}
@@ -0,0 +1,233 @@
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use bevy::prelude::*;
+
+use super::Lobby;
+use crate::common::tile::Tiles;
+use crate::common::transport::NetworkStats;
+
+/// How long a scraper gets to send its request and read the response. Scrapes are answered one
+/// at a time, so a stalled connection would otherwise hold up every later one.
+const SCRAPE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Upper bounds of the frame duration histogram's buckets, in seconds.
+const FRAME_BUCKETS: [f64; 10] = [
+    0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 1.0,
+];
+
+#[derive(Default)]
+struct Histogram {
+    /// How many observations fell in each bucket, not counting earlier buckets.
+    buckets: [u64; FRAME_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, value: f64) {
+        if let Some(bucket) = FRAME_BUCKETS.iter().position(|&bound| value <= bound) {
+            self.buckets[bucket] += 1;
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+/// What the game last reported, read whenever the endpoint is scraped.
+#[derive(Default)]
+struct Gauges {
+    players: usize,
+    spectators: usize,
+    tiles: usize,
+    entities: usize,
+    frame_duration: Histogram,
+}
+
+#[derive(Clone)]
+struct Metrics {
+    gauges: Arc<Mutex<Gauges>>,
+    stats: NetworkStats,
+}
+
+impl Metrics {
+    fn gauges(&self) -> MutexGuard<'_, Gauges> {
+        self.gauges
+            .lock()
+            .expect("No one panics while holding the metrics")
+    }
+
+    /// All metrics in the Prometheus text format.
+    fn render(&self) -> String {
+        let mut out = String::new();
+        {
+            let gauges = self.gauges();
+            metric(
+                &mut out,
+                "game_connected_clients",
+                "gauge",
+                "Connected clients.",
+            );
+            let _ = writeln!(
+                out,
+                "game_connected_clients{{kind=\"player\"}} {}",
+                gauges.players
+            );
+            let _ = writeln!(
+                out,
+                "game_connected_clients{{kind=\"spectator\"}} {}",
+                gauges.spectators
+            );
+            metric(&mut out, "game_tiles", "gauge", "Tiles in the world.");
+            let _ = writeln!(out, "game_tiles {}", gauges.tiles);
+            metric(
+                &mut out,
+                "game_entities",
+                "gauge",
+                "Entities in the server's world.",
+            );
+            let _ = writeln!(out, "game_entities {}", gauges.entities);
+
+            let histogram = &gauges.frame_duration;
+            metric(
+                &mut out,
+                "game_frame_duration_seconds",
+                "histogram",
+                "Time spent running one server frame, networking included.",
+            );
+            let mut cumulative = 0;
+            for (bound, count) in FRAME_BUCKETS.iter().zip(histogram.buckets) {
+                cumulative += count;
+                let _ = writeln!(
+                    out,
+                    "game_frame_duration_seconds_bucket{{le=\"{bound}\"}} {cumulative}"
+                );
+            }
+            let _ = writeln!(
+                out,
+                "game_frame_duration_seconds_bucket{{le=\"+Inf\"}} {}",
+                histogram.count
+            );
+            let _ = writeln!(out, "game_frame_duration_seconds_sum {}", histogram.sum);
+            let _ = writeln!(out, "game_frame_duration_seconds_count {}", histogram.count);
+        }
+
+        let totals = self.stats.totals();
+        for (direction, traffic) in [("sent", &totals.sent), ("received", &totals.received)] {
+            let name = format!("game_messages_{direction}_total");
+            metric(
+                &mut out,
+                &name,
+                "counter",
+                &format!("Messages {direction}."),
+            );
+            for (&(channel_id, message), traffic) in traffic {
+                let _ = writeln!(
+                    out,
+                    "{name}{{channel=\"{channel_id}\",message=\"{message}\"}} {}",
+                    traffic.messages
+                );
+            }
+
+            let name = format!("game_bytes_{direction}_total");
+            metric(
+                &mut out,
+                &name,
+                "counter",
+                &format!("Message bytes {direction}."),
+            );
+            let mut channels = BTreeMap::<u8, u64>::new();
+            for (&(channel_id, _), traffic) in traffic {
+                *channels.entry(channel_id).or_default() += traffic.bytes;
+            }
+            for (channel_id, bytes) in channels {
+                let _ = writeln!(out, "{name}{{channel=\"{channel_id}\"}} {bytes}");
+            }
+        }
+        out
+    }
+}
+
+fn metric(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+/// When the current frame started.
+struct FrameStart(Instant);
+
+/// Serves Prometheus metrics over HTTP at `/metrics` on the given address.
+pub struct MetricsPlugin(pub SocketAddr);
+
+impl Plugin for MetricsPlugin {
+    fn build(&self, app: &mut App) {
+        let metrics = Metrics {
+            gauges: default(),
+            stats: app.world.resource::<NetworkStats>().clone(),
+        };
+        let listener = TcpListener::bind(self.0)
+            .unwrap_or_else(|e| panic!("Can't serve metrics on {}: {e}", self.0));
+        info!("Serving metrics on http://{}/metrics", self.0);
+        let served = metrics.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                respond(stream, &served);
+            }
+        });
+
+        app.insert_resource(metrics)
+            .insert_resource(FrameStart(Instant::now()))
+            .add_system_to_stage(CoreStage::First, start_frame)
+            .add_system_to_stage(CoreStage::Last, record_frame);
+    }
+}
+
+/// Answers one scrape. Anything but a `GET /metrics` gets a 404.
+fn respond(mut stream: TcpStream, metrics: &Metrics) {
+    if stream.set_read_timeout(Some(SCRAPE_TIMEOUT)).is_err()
+        || stream.set_write_timeout(Some(SCRAPE_TIMEOUT)).is_err()
+    {
+        return;
+    }
+    let mut request = [0; 1024];
+    let read = match stream.read(&mut request) {
+        Ok(read) => read,
+        Err(_) => return,
+    };
+    let request = String::from_utf8_lossy(&request[..read]);
+    let (status, body) = if request.starts_with("GET /metrics ") {
+        ("200 OK", metrics.render())
+    } else {
+        ("404 Not Found", String::new())
+    };
+    let _ = write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\n\
+         Connection: close\r\n\r\n{body}",
+        body.len()
+    );
+}
+
+fn start_frame(mut start: ResMut<FrameStart>) {
+    start.0 = Instant::now();
+}
+
+fn record_frame(
+    start: Res<FrameStart>,
+    metrics: Res<Metrics>,
+    lobby: Res<Lobby>,
+    tiles: Res<Tiles>,
+    entities: Query<Entity>,
+) {
+    let mut gauges = metrics.gauges();
+    gauges
+        .frame_duration
+        .observe(start.0.elapsed().as_secs_f64());
+    gauges.players = lobby.players.len();
+    gauges.spectators = lobby.spectators.len();
+    gauges.tiles = tiles.len();
+    gauges.entities = entities.iter().count();
+}
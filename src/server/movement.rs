@@ -0,0 +1,181 @@
+use bevy::prelude::*;
+
+use super::config::ServerSettings;
+use super::{Lobby, MovedPlayers};
+use crate::common::collision::move_and_slide;
+use crate::common::message::{RenetServerExt, ServerUnreliable};
+use crate::common::physics::{platformer_step, MoveInput, MovementMode, FIXED_DT, FLY_SPEED};
+use crate::common::player::PlayerLocation;
+use crate::common::tile::{TileRegistry, Tiles};
+use crate::common::transport::NetServer;
+
+/// How far the server may disagree with a client's position before correcting it.
+const POSITION_TOLERANCE: f32 = 1.0;
+/// Fastest a flying player moves, diagonally at full speed on both axes.
+const MAX_FLY_SPEED: f32 = FLY_SPEED * std::f32::consts::SQRT_2;
+/// Seconds of flying a player can save up, so movement that arrives in bursts isn't cut short.
+const MOVEMENT_BURST_SECONDS: f32 = 0.25;
+/// The most distance a flying player can have saved up.
+pub const MAX_MOVEMENT_BUDGET: f32 = MAX_FLY_SPEED * MOVEMENT_BURST_SECONDS;
+/// Most platformer inputs simulated from a single message.
+const MAX_INPUTS_PER_MESSAGE: usize = 8;
+/// Platformer inputs a player can save up, so inputs that arrive in bursts aren't dropped. A
+/// player who sends nothing for longer is simulated standing still.
+const INPUT_BUFFER_TICKS: u32 = 8;
+/// Furthest a client's tick may be ahead of the last one accepted from it, a second.
+const MAX_TICKS_AHEAD: u32 = 60;
+
+#[cfg(test)]
+impl Lobby {
+    /// The last platformer tick simulated for a player.
+    pub fn platformer_tick(&self, client_id: u64) -> Option<u32> {
+        Some(self.profiles.get(&client_id)?.tick)
+    }
+}
+
+/// Gives every player another tick's worth of movement.
+pub fn regain_movement(mut lobby: ResMut<Lobby>) {
+    for profile in lobby.profiles.values_mut() {
+        profile.movement_budget =
+            (profile.movement_budget + MAX_FLY_SPEED * FIXED_DT).min(MAX_MOVEMENT_BUDGET);
+        profile.input_budget += 1;
+    }
+}
+
+/// Moves a flying player towards where their client says they are, no further than they could
+/// have flown and stopping at solid tiles. The client is corrected when it ends up elsewhere.
+pub fn move_flying(
+    lobby: &mut Lobby,
+    server: &mut NetServer,
+    tiles: &Tiles,
+    registry: &TileRegistry,
+    moved: &mut MovedPlayers,
+    client_id: u64,
+    target: Vec2,
+) {
+    let (player, profile) = match (
+        lobby.players.get_mut(&client_id),
+        lobby.profiles.get_mut(&client_id),
+    ) {
+        (Some(player), Some(profile)) => (player, profile),
+        _ => return,
+    };
+
+    let delta = if target.is_finite() {
+        (target - player.pos).clamp_length_max(profile.movement_budget)
+    } else {
+        warn!(client_id, "Client moved to an impossible position");
+        Vec2::ZERO
+    };
+    profile.movement_budget = (profile.movement_budget - delta.length()).max(0.0);
+
+    let resolved = move_and_slide(tiles, registry, player.pos, delta);
+    player.pos = resolved;
+    moved.0.insert(client_id);
+    if !target.is_finite() || resolved.distance(target) > POSITION_TOLERANCE {
+        server.send_to(
+            client_id,
+            ServerUnreliable::PositionCorrected(PlayerLocation(resolved)),
+        );
+    }
+}
+
+/// Simulates the platformer inputs in a message that the server hasn't simulated yet, at most
+/// one per server tick plus [`INPUT_BUFFER_TICKS`]. Inputs beyond that are dropped and the
+/// client corrected, so sending inputs faster doesn't make anyone move faster.
+#[allow(clippy::too_many_arguments)]
+pub fn apply_platformer_inputs(
+    lobby: &mut Lobby,
+    server: &mut NetServer,
+    tiles: &Tiles,
+    registry: &TileRegistry,
+    moved: &mut MovedPlayers,
+    client_id: u64,
+    tick: u32,
+    inputs: &[MoveInput],
+) {
+    let (player, profile) = match (
+        lobby.players.get_mut(&client_id),
+        lobby.profiles.get_mut(&client_id),
+    ) {
+        (Some(player), Some(profile)) => (player, profile),
+        _ => return,
+    };
+    if tick <= profile.tick {
+        return;
+    }
+    if tick - profile.tick > MAX_TICKS_AHEAD {
+        warn!(
+            client_id,
+            tick,
+            last = profile.tick,
+            "Client sent inputs from the future"
+        );
+        return;
+    }
+    if profile.respawn_at.is_some() {
+        // Dead players don't move, but their ticks still pass.
+        profile.tick = tick;
+        return;
+    }
+
+    let fresh = ((tick - profile.tick) as usize)
+        .min(inputs.len())
+        .min(MAX_INPUTS_PER_MESSAGE);
+    for &input in &inputs[inputs.len() - fresh..] {
+        if profile.input_budget == 0 {
+            break;
+        }
+        profile.input_budget -= 1;
+        player.pos = platformer_step(tiles, registry, player.pos, &mut profile.body, input);
+    }
+    profile.tick = tick;
+
+    moved.0.insert(client_id);
+    server.send_to(
+        client_id,
+        ServerUnreliable::PlatformerState(profile.tick, player.pos, profile.body),
+    );
+}
+
+/// Simulates players whose inputs stopped arriving as if they let go of every button, so they
+/// fall rather than hang in the air. Their client's ticks are taken to have passed with them.
+pub fn step_idle_platformers(
+    mut lobby: ResMut<Lobby>,
+    mut server: ResMut<NetServer>,
+    mut moved: ResMut<MovedPlayers>,
+    tiles: Res<Tiles>,
+    registry: Res<TileRegistry>,
+    settings: Res<ServerSettings>,
+) {
+    let platformer = settings.rules.movement == MovementMode::Platformer;
+    let lobby = &mut *lobby;
+    for (&client_id, profile) in &mut lobby.profiles {
+        let player = match lobby.players.get_mut(&client_id) {
+            Some(player) if platformer && profile.respawn_at.is_none() => player,
+            _ => {
+                profile.input_budget = profile.input_budget.min(INPUT_BUFFER_TICKS);
+                continue;
+            }
+        };
+        if profile.input_budget <= INPUT_BUFFER_TICKS {
+            continue;
+        }
+        while profile.input_budget > INPUT_BUFFER_TICKS {
+            profile.input_budget -= 1;
+            profile.tick = profile.tick.wrapping_add(1);
+            player.pos = platformer_step(
+                &tiles,
+                &registry,
+                player.pos,
+                &mut profile.body,
+                MoveInput::default(),
+            );
+        }
+        moved.0.insert(client_id);
+        server.send_to(
+            client_id,
+            ServerUnreliable::PlatformerState(profile.tick, player.pos, profile.body),
+        );
+    }
+}
@@ -0,0 +1,108 @@
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::simulation::ServerTick;
+use super::{Lobby, Profile};
+use crate::common::inventory::Inventory;
+use crate::common::physics::FIXED_DT;
+
+pub const PLAYER_SAVE_DIR: &str = "saves/players";
+/// Ticks between writes of saves that changed, thirty seconds rounded to whole ticks. Players
+/// who leave are saved right away.
+const SAVE_INTERVAL_TICKS: u32 = (30.0 / FIXED_DT + 0.5) as u32;
+
+/// Everything about a player that outlives their connection.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PlayerSave {
+    #[serde(default)]
+    pub inventory: Inventory,
+    /// Where the player was when they left, if they were alive.
+    #[serde(default)]
+    pub position: Option<Vec2>,
+}
+
+/// Where a player's save is kept. Bytes of the name that aren't safe in a file name are
+/// percent-escaped, so no two names share a file.
+fn save_path(dir: &Path, name: &str) -> PathBuf {
+    let mut file_name = String::new();
+    for byte in name.bytes() {
+        if byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_' {
+            file_name.push(byte as char);
+        } else {
+            write!(file_name, "%{byte:02X}").expect("Writing to a string never fails");
+        }
+    }
+    dir.join(format!("{file_name}.ron"))
+}
+
+impl PlayerSave {
+    pub fn load(dir: &Path, name: &str) -> Option<Self> {
+        let path = save_path(dir, name);
+        let data = fs::read_to_string(&path).ok()?;
+        match ron::from_str(&data) {
+            Ok(save) => Some(save),
+            Err(e) => {
+                warn!(path = %path.display(), error = %e, "Ignoring corrupt save");
+                None
+            }
+        }
+    }
+
+    pub fn to_ron(&self) -> String {
+        ron::ser::to_string_pretty(self, Default::default())
+            .expect("Player saves are always serializable")
+    }
+
+    pub fn store(&self, dir: &Path, name: &str) {
+        let path = save_path(dir, name);
+        let data = self.to_ron();
+        if let Err(e) = fs::create_dir_all(dir).and_then(|_| fs::write(&path, data)) {
+            error!(path = %path.display(), error = %e, "Could not write save");
+        }
+    }
+}
+
+impl Profile {
+    /// Keeps the player's save up to date with where they are, or that they're dead.
+    pub fn record_position(&mut self, pos: Vec2) {
+        let position = match self.respawn_at {
+            None => Some(pos),
+            Some(_) => None,
+        };
+        if self.save.position != position {
+            self.save.position = position;
+            self.dirty = true;
+        }
+    }
+}
+
+/// Writes the saves that changed since the last time, every [`SAVE_INTERVAL_TICKS`], so busy
+/// players don't cost a file write per block and a crash loses at most that much.
+pub fn save_players(mut lobby: ResMut<Lobby>, tick: Res<ServerTick>) {
+    if tick.0 % SAVE_INTERVAL_TICKS != 0 {
+        return;
+    }
+    let Lobby {
+        players,
+        profiles,
+        save_dir,
+        ..
+    } = &mut *lobby;
+    let dir = match save_dir {
+        Some(dir) => dir,
+        None => return,
+    };
+    for (id, profile) in profiles.iter_mut() {
+        if let Some(player) = players.get(id) {
+            profile.record_position(player.pos);
+        }
+    }
+    for profile in profiles.values_mut().filter(|profile| profile.dirty) {
+        profile.save.store(dir, &profile.name);
+        profile.dirty = false;
+    }
+}
@@ -0,0 +1,35 @@
+use bevy::prelude::*;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::common::physics::FIXED_DT;
+
+/// How many ticks the server has simulated. Every tick is [`FIXED_DT`] long however long the
+/// frame really took, so the simulation only depends on what clients send.
+#[derive(Default)]
+pub struct ServerTick(pub u32);
+
+impl ServerTick {
+    /// Simulated seconds since the server started.
+    pub fn seconds(&self) -> f64 {
+        self.0 as f64 * FIXED_DT as f64
+    }
+}
+
+/// Where all of the simulation's randomness comes from.
+#[derive(Deref, DerefMut)]
+pub struct ServerRng(StdRng);
+
+impl ServerRng {
+    /// Seeds the simulation with `seed`, or a random seed that is logged so the run can be
+    /// repeated.
+    pub fn new(seed: Option<u64>) -> Self {
+        let seed = seed.unwrap_or_else(rand::random);
+        info!(seed, "Seeded the simulation");
+        Self(StdRng::seed_from_u64(seed))
+    }
+}
+
+pub fn advance_tick(mut tick: ResMut<ServerTick>) {
+    tick.0 += 1;
+}
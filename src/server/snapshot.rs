@@ -0,0 +1,68 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::hash::Hasher;
+
+use bevy::prelude::*;
+use serde::Serialize;
+
+use super::simulation::ServerTick;
+use super::{Lobby, Mining, Profile, TileDamage};
+use crate::common::message::NetworkId;
+use crate::common::player::PlayerSyncData;
+use crate::common::tile::{TileKind, Tiles};
+
+/// Everything the simulation decides, in an order that doesn't depend on hashing.
+#[derive(Serialize)]
+struct Snapshot<'a> {
+    tick: u32,
+    tiles: Vec<(IVec2, NetworkId, TileKind)>,
+    players: BTreeMap<u64, &'a PlayerSyncData>,
+    profiles: BTreeMap<u64, &'a Profile>,
+    damaged_tiles: BTreeMap<u64, &'a TileDamage>,
+    last_hit_by: BTreeMap<u64, f64>,
+}
+
+/// A hash of the simulation's state. Two servers fed the same inputs with the same seed hash
+/// the same.
+pub fn state_hash(tick: &ServerTick, lobby: &Lobby, tiles: &Tiles, mining: &Mining) -> u64 {
+    let mut tile_list: Vec<_> = tiles
+        .iter()
+        .map(|(&pos, &(id, kind))| (pos, id, kind))
+        .collect();
+    tile_list.sort_by_key(|&(pos, ..)| (pos.x, pos.y));
+    let snapshot = Snapshot {
+        tick: tick.0,
+        tiles: tile_list,
+        players: lobby
+            .players
+            .iter()
+            .map(|(&id, player)| (id, player))
+            .collect(),
+        profiles: lobby
+            .profiles
+            .iter()
+            .map(|(&id, profile)| (id, profile))
+            .collect(),
+        damaged_tiles: mining
+            .tiles
+            .iter()
+            .map(|(id, damage)| (id.to_bits(), damage))
+            .collect(),
+        last_hit_by: mining.last_hit_by.iter().map(|(&id, &t)| (id, t)).collect(),
+    };
+    let bytes = bincode::serialize(&snapshot).expect("The state is always serializable");
+    let mut hasher = DefaultHasher::new();
+    hasher.write(&bytes);
+    hasher.finish()
+}
+
+/// [`state_hash`] of a server app's world.
+#[cfg(test)]
+pub fn world_hash(world: &World) -> u64 {
+    state_hash(
+        world.resource(),
+        world.resource(),
+        world.resource(),
+        world.resource(),
+    )
+}
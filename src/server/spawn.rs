@@ -0,0 +1,61 @@
+use std::sync::OnceLock;
+
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::common::collision::Aabb;
+use crate::common::tile::{TileRegistry, Tiles, TILE_SIZE};
+
+/// How many tiles away from the requested spot a safe position is searched for.
+const SEARCH_RADIUS: i32 = 16;
+
+/// A random spawn tile in the world, or the origin when there are none.
+pub fn pick_spawn_point(tiles: &Tiles, registry: &TileRegistry, rng: &mut impl Rng) -> Vec2 {
+    let mut points: Vec<IVec2> = tiles
+        .iter()
+        .filter(|(_, &(_, kind))| matches!(registry.get(kind), Some(def) if def.spawn))
+        .map(|(&pos, _)| pos)
+        .collect();
+    if points.is_empty() {
+        return Vec2::ZERO;
+    }
+    // Sorted so the same seed always picks the same spawn point.
+    points.sort_unstable_by_key(|pos| (pos.x, pos.y));
+    points[rng.gen_range(0..points.len())].as_vec2() * TILE_SIZE
+}
+
+/// Every offset within [`SEARCH_RADIUS`], nearest first and higher first among equals. Worked out
+/// once, since every spawn walks the same list.
+fn search_offsets() -> &'static [IVec2] {
+    static OFFSETS: OnceLock<Vec<IVec2>> = OnceLock::new();
+    OFFSETS.get_or_init(|| {
+        let mut offsets: Vec<IVec2> = (-SEARCH_RADIUS..=SEARCH_RADIUS)
+            .flat_map(|y| (-SEARCH_RADIUS..=SEARCH_RADIUS).map(move |x| IVec2::new(x, y)))
+            .collect();
+        offsets.sort_by_key(|offset| (offset.x * offset.x + offset.y * offset.y, -offset.y));
+        offsets
+    })
+}
+
+/// Whether a player at `pos` would be free of solid and harmful tiles.
+fn is_safe(tiles: &Tiles, registry: &TileRegistry, pos: Vec2) -> bool {
+    let bounds = Aabb::player(pos);
+    bounds
+        .grid_cells()
+        .filter(|&cell| bounds.overlaps(&Aabb::tile(cell)))
+        .filter_map(|cell| tiles.get(&cell))
+        .all(
+            |&(_, kind)| matches!(registry.get(kind), Some(def) if !def.solid && def.damage <= 0.0),
+        )
+}
+
+/// The safe position closest to `pos`, searching outwards one tile at a time.
+///
+/// Falls back to `pos` itself when everything nearby is blocked.
+pub fn find_safe_spawn(tiles: &Tiles, registry: &TileRegistry, pos: Vec2) -> Vec2 {
+    search_offsets()
+        .iter()
+        .map(|offset| pos + offset.as_vec2() * TILE_SIZE)
+        .find(|&candidate| is_safe(tiles, registry, candidate))
+        .unwrap_or(pos)
+}
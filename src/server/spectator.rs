@@ -0,0 +1,23 @@
+use super::config::ServerSettings;
+use super::Lobby;
+
+impl Lobby {
+    pub fn is_spectator(&self, client_id: u64) -> bool {
+        self.spectators.contains_key(&client_id)
+    }
+
+    /// Whether another player, or spectator if `spectator` is set, would exceed the server's caps.
+    pub fn is_full(&self, settings: &ServerSettings, spectator: bool) -> bool {
+        let players = if settings.spectators_take_player_slots {
+            self.players.len() + self.spectators.len()
+        } else {
+            self.players.len()
+        };
+        if spectator {
+            self.spectators.len() >= settings.max_spectators
+                || settings.spectators_take_player_slots && players >= settings.max_players
+        } else {
+            players >= settings.max_players
+        }
+    }
+}
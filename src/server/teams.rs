@@ -0,0 +1,75 @@
+use bevy::prelude::*;
+use rand::Rng;
+
+use super::config::ServerSettings;
+use super::Lobby;
+use crate::common::message::{RenetServerExt, ServerReliable};
+use crate::common::team::TeamId;
+use crate::common::transport::NetServer;
+
+impl Lobby {
+    pub fn team(&self, client_id: u64) -> Option<TeamId> {
+        self.players.get(&client_id)?.team
+    }
+
+    /// Whether both players are on the same team. Players without a team are on nobody's side.
+    pub fn same_team(&self, a: u64, b: u64) -> bool {
+        matches!((self.team(a), self.team(b)), (Some(a), Some(b)) if a == b)
+    }
+
+    fn team_size(&self, team: TeamId) -> usize {
+        self.players
+            .values()
+            .filter(|player| player.team == Some(team))
+            .count()
+    }
+
+    /// The team with the fewest players, preferring earlier teams on a tie.
+    pub fn smallest_team(&self, settings: &ServerSettings) -> Option<TeamId> {
+        (0..settings.rules.teams.len())
+            .map(|i| TeamId(i as u8))
+            .min_by_key(|&team| self.team_size(team))
+    }
+}
+
+/// The colour a player on `team` is drawn with.
+pub fn team_color(settings: &ServerSettings, team: Option<TeamId>, rng: &mut impl Rng) -> Color {
+    match team.and_then(|team| settings.rules.team(team)) {
+        Some(def) => def.color,
+        None => Color::rgb(rng.gen(), rng.gen(), rng.gen()),
+    }
+}
+
+pub fn change_team(
+    lobby: &mut Lobby,
+    server: &mut NetServer,
+    settings: &ServerSettings,
+    client_id: u64,
+    team: TeamId,
+) {
+    let def = match settings.rules.team(team) {
+        Some(def) => def,
+        None => return,
+    };
+    let current = lobby.team(client_id);
+    if current == Some(team) {
+        return;
+    }
+    if let Some(current) = current {
+        if settings.balance_teams && lobby.team_size(team) >= lobby.team_size(current) {
+            info!(
+                client_id,
+                "Refused a team change that would unbalance teams"
+            );
+            return;
+        }
+    }
+
+    let color = def.color;
+    if let Some(player) = lobby.players.get_mut(&client_id) {
+        player.team = Some(team);
+        player.color = color;
+        server.broadcast(ServerReliable::PlayerTeamChanged(client_id, team, color));
+        info!(client_id, team = team.0, "Client changed teams");
+    }
+}
@@ -0,0 +1,1513 @@
+//! Multiplayer integration tests. A server and several clients run in one process over an
+//! in-memory transport and are stepped frame by frame, so every run sees the same messages in
+//! the same order. Their clocks advance one server tick per frame however fast the test runs.
+//! Tests of bad connections use renet over UDP on localhost instead, through a seeded proxy.
+
+use std::net::{SocketAddr, UdpSocket};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use bevy::asset::AssetPlugin;
+use bevy::ecs::event::ManualEventReader;
+use bevy::math::Vec3Swizzles;
+use bevy::prelude::*;
+use bevy::time::{create_time_channels, TimeSender};
+use bevy_renet::renet::{
+    ClientAuthentication, RenetClient, RenetConnectionConfig, RenetServer, ServerAuthentication,
+    ServerConfig,
+};
+
+use crate::client::{self, NetworkPlugin};
+use crate::common::inventory::Inventory;
+use crate::common::message::{
+    message_name, ClientReliable, ClientUnreliable, NetworkEvent, NetworkId, RenetClientExt,
+    SendOverRenet, ServerReliable, ServerUnreliable, PROTOCOL_ID,
+};
+use crate::common::physics::{MoveInput, MovementMode, FIXED_DT};
+use crate::common::player::{
+    ConnectInfo, Health, MovementBatch, PlayerIndex, PlayerLocation, PlayerTeam, MAX_HEALTH,
+    POSITION_STEP,
+};
+use crate::common::replay::{load_replay, Recipient, ReplayEvent};
+use crate::common::rules::GameRules;
+use crate::common::team::TeamId;
+use crate::common::tile::{TileKind, TileRegistry, TileTexture, Tiles, WorldData, TILE_SIZE};
+use crate::common::transfer::{Chunk, Downloads, Received, TransferKind, CHUNK_SIZE};
+use crate::common::transport::{
+    pack, ClientTransport, Compressed, Compression, LinkConditioner, LinkConditions, LinkProxy,
+    MemoryServer, Metered, NetClient, NetServer, NetworkStats, Playback, Recorder, ServerTransport,
+};
+use crate::server::{self, server_app, world_hash, ServerSettings, ServerTick};
+
+/// Frames it takes a message to reach the server and the server's answer to reach every client.
+const ROUND_TRIP_FRAMES: usize = 3;
+/// Frames a reliable message may take over the lossiest test connection, resends included.
+const MAX_RESEND_FRAMES: usize = 120;
+/// Frames a client may take to download the world before a test gives up on it.
+const MAX_SYNC_FRAMES: usize = 2000;
+/// Every test server uses the same seed, so tests never depend on luck.
+const SEED: u64 = 1;
+
+fn settings() -> ServerSettings {
+    ServerSettings {
+        save_dir: None,
+        seed: Some(SEED),
+        ..default()
+    }
+}
+
+/// A directory of its own for a test's files.
+fn test_dir(test: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("{test}-{}", std::process::id()))
+}
+
+/// Makes the app's clock only move when [`advance_clock`] says so.
+fn stop_clock(app: &mut App) {
+    let (sender, receiver) = create_time_channels();
+    app.insert_resource(sender).insert_resource(receiver);
+}
+
+/// Sets the app's clock to `now` for its next update.
+fn advance_clock(app: &mut App, now: Instant) {
+    app.world
+        .resource::<TimeSender>()
+        .0
+        .send(now)
+        .expect("The app holds its own receiver");
+}
+
+/// How a [`Game`]'s clients reach its server.
+enum Network {
+    Memory(MemoryServer),
+    /// Renet over UDP, through a proxy making the connection worse.
+    Udp(Box<LinkProxy>),
+}
+
+/// A renet server on localhost behind a proxy applying `conditions`, which clients connect to.
+fn udp_server(conditions: LinkConditions) -> (RenetServer, LinkProxy) {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let conditioner = LinkConditioner::new(LinkConditions {
+        seed: Some(SEED),
+        ..conditions
+    });
+    let proxy = LinkProxy::bind(
+        "127.0.0.1:0".parse().unwrap(),
+        socket.local_addr().unwrap(),
+        conditioner,
+    )
+    .unwrap();
+    let config = ServerConfig::new(
+        64,
+        PROTOCOL_ID,
+        proxy.addr(),
+        ServerAuthentication::Unsecure,
+    );
+    let server = RenetServer::new(
+        Duration::ZERO,
+        config,
+        RenetConnectionConfig::default(),
+        socket,
+    )
+    .unwrap();
+    (server, proxy)
+}
+
+/// A renet client that connects to the server behind the proxy at `addr`.
+fn udp_client(addr: SocketAddr, client_id: u64, info: &ConnectInfo) -> RenetClient {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let authentication = ClientAuthentication::Unsecure {
+        protocol_id: PROTOCOL_ID,
+        client_id,
+        server_addr: addr,
+        user_data: Some(info.encode()),
+    };
+    let config = RenetConnectionConfig::default();
+    RenetClient::new(Duration::ZERO, socket, client_id, config, authentication).unwrap()
+}
+
+/// Wraps the server's transport the way [`server::server`] does.
+fn net_server(
+    transport: impl ServerTransport,
+    stats: &NetworkStats,
+    settings: &ServerSettings,
+    replay_dir: Option<&Path>,
+) -> NetServer {
+    let server = Metered::new(transport, stats.clone());
+    let server = Compressed::new(server, settings.compression.clone());
+    match replay_dir {
+        Some(dir) => NetServer::new(Recorder::create(server, dir)),
+        None => NetServer::new(server),
+    }
+}
+
+struct Game {
+    network: Network,
+    stats: NetworkStats,
+    server: App,
+    clients: Vec<App>,
+    /// What every app's clock reads.
+    now: Instant,
+}
+
+impl Game {
+    fn new() -> Self {
+        Self::build(settings(), None, None)
+    }
+
+    /// A game played over UDP, where every packet between the server and a client goes through
+    /// the given connection.
+    fn with_conditions(conditions: LinkConditions) -> Self {
+        Self::build(settings(), Some(conditions), None)
+    }
+
+    /// A game whose server records a replay into `dir`.
+    fn recording(dir: &Path) -> Self {
+        Self::build(settings(), None, Some(dir))
+    }
+
+    /// A game played as a side-scrolling platformer.
+    fn platformer() -> Self {
+        let mut settings = settings();
+        settings.rules.movement = MovementMode::Platformer;
+        Self::build(settings, None, None)
+    }
+
+    /// A game in memory, or over UDP with `conditions`.
+    fn build(
+        settings: ServerSettings,
+        conditions: Option<LinkConditions>,
+        replay_dir: Option<&Path>,
+    ) -> Self {
+        let stats = NetworkStats::default();
+        let (network, server) = match conditions {
+            Some(conditions) => {
+                let (server, proxy) = udp_server(conditions);
+                let server = net_server(server, &stats, &settings, replay_dir);
+                (Network::Udp(Box::new(proxy)), server)
+            }
+            None => {
+                let transport = MemoryServer::new();
+                let server = net_server(transport.clone(), &stats, &settings, replay_dir);
+                (Network::Memory(transport), server)
+            }
+        };
+        let mut server = server_app(server, settings);
+        let now = Instant::now();
+        stop_clock(&mut server);
+        advance_clock(&mut server, now);
+        server.update();
+        Self {
+            network,
+            stats,
+            server,
+            clients: Vec::new(),
+            now,
+        }
+    }
+
+    /// Connects a headless client and waits until the server has synced it.
+    fn connect(&mut self, name: &str, spectator: bool) -> usize {
+        let client_id = self.clients.len() as u64 + 1;
+        let info = ConnectInfo {
+            name: name.to_string(),
+            spectator,
+            compression: Compression::ALL.to_vec(),
+        };
+        let transport = match &self.network {
+            Network::Memory(server) => NetClient::new(Compressed::new(
+                server.connect(client_id, info.encode()),
+                info.compression.clone(),
+            )),
+            Network::Udp(proxy) => NetClient::new(Compressed::new(
+                udp_client(proxy.addr(), client_id, &info),
+                info.compression.clone(),
+            )),
+        };
+        let mut client = App::new();
+        client
+            .add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin)
+            .add_plugin(NetworkPlugin)
+            .insert_resource(transport)
+            .insert_resource(info);
+        stop_clock(&mut client);
+        self.clients.push(client);
+        self.step(ROUND_TRIP_FRAMES);
+        let client = self.clients.len() - 1;
+        // The world may take many frames over a bad connection.
+        for _ in 0..MAX_SYNC_FRAMES {
+            if self.clients[client]
+                .world
+                .resource::<client::WorldSync>()
+                .loaded
+            {
+                break;
+            }
+            self.step(1);
+        }
+        client
+    }
+
+    fn step(&mut self, frames: usize) {
+        for _ in 0..frames {
+            let frame = Duration::from_secs_f32(FIXED_DT);
+            self.now += frame;
+            if let Network::Udp(proxy) = &mut self.network {
+                proxy.update(frame).unwrap();
+            }
+            advance_clock(&mut self.server, self.now);
+            self.server.update();
+            for client in &mut self.clients {
+                advance_clock(client, self.now);
+                client.update();
+            }
+        }
+    }
+
+    fn client_id(&self, client: usize) -> u64 {
+        self.clients[client]
+            .world
+            .resource::<NetClient>()
+            .client_id()
+    }
+
+    fn mine(&mut self, client: usize, tile: NetworkId) {
+        self.clients[client]
+            .world
+            .resource_mut::<NetClient>()
+            .send(ClientUnreliable::MineBlock(tile, 10.0));
+        self.step(ROUND_TRIP_FRAMES);
+    }
+
+    fn place(&mut self, client: usize, pos: IVec2, kind: TileKind) {
+        self.clients[client]
+            .world
+            .resource_mut::<NetClient>()
+            .send_event(NetworkEvent::SpawnBlock(pos, kind));
+        self.step(ROUND_TRIP_FRAMES);
+    }
+
+    fn server_tiles(&self) -> &Tiles {
+        self.server.world.resource::<Tiles>()
+    }
+
+    fn client_tiles(&self, client: usize) -> &Tiles {
+        self.clients[client].world.resource::<Tiles>()
+    }
+
+    fn player_pos(&self, client: usize) -> Vec2 {
+        let id = self.client_id(client);
+        self.server.world.resource::<server::Lobby>().players[&id].pos
+    }
+
+    /// Moves a player on the server only, as if they had walked there.
+    fn teleport(&mut self, client: usize, pos: Vec2) {
+        let id = self.client_id(client);
+        let mut lobby = self.server.world.resource_mut::<server::Lobby>();
+        lobby.players.get_mut(&id).unwrap().pos = pos;
+    }
+
+    fn health(&self, client: usize) -> f32 {
+        let id = self.client_id(client);
+        self.server.world.resource::<server::Lobby>().players[&id].health
+    }
+
+    /// How much health `viewer` sees `client` with.
+    fn seen_health(&self, viewer: usize, client: usize) -> f32 {
+        let id = self.client_id(client);
+        let world = &self.clients[viewer].world;
+        let player = world.resource::<client::Lobby>().players[&id];
+        world.get::<Health>(player).unwrap().0
+    }
+
+    fn is_alive(&self, client: usize) -> bool {
+        let id = self.client_id(client);
+        self.server.world.resource::<server::Lobby>().is_alive(id)
+    }
+
+    fn team(&self, client: usize) -> Option<TeamId> {
+        let id = self.client_id(client);
+        self.server.world.resource::<server::Lobby>().team(id)
+    }
+
+    fn kind(&self, name: &str) -> TileKind {
+        self.server
+            .world
+            .resource::<TileRegistry>()
+            .kind(name)
+            .unwrap()
+    }
+
+    /// A tile of the named kind within the client's reach.
+    fn tile_in_reach(&self, client: usize, name: &str) -> (IVec2, NetworkId) {
+        let kind = self.kind(name);
+        let rules = self.server.world.resource::<ServerSettings>().rules.clone();
+        let pos = self.player_pos(client);
+        self.server_tiles()
+            .iter()
+            .find(|(&tile, &(_, tile_kind))| tile_kind == kind && rules.in_reach(pos, tile))
+            .map(|(&tile, &(id, _))| (tile, id))
+            .expect("The starting world has a tile in reach")
+    }
+}
+
+#[test]
+fn clients_share_the_world_and_the_lobby() {
+    let mut game = Game::new();
+    for name in ["alice", "bob", "carol"] {
+        game.connect(name, false);
+    }
+
+    let server_lobby = game.server.world.resource::<server::Lobby>();
+    assert_eq!(server_lobby.players.len(), 3);
+    for client in 0..3 {
+        let lobby = game.clients[client].world.resource::<client::Lobby>();
+        assert_eq!(lobby.players.len(), 3);
+        for id in server_lobby.players.keys() {
+            assert!(lobby.players.contains_key(id));
+        }
+        assert_eq!(game.client_tiles(client).0, game.server_tiles().0);
+        assert_eq!(
+            game.clients[client].world.resource::<GameRules>(),
+            &game.server.world.resource::<ServerSettings>().rules
+        );
+    }
+}
+
+#[test]
+fn clients_use_the_servers_tile_registry() {
+    let mut game = Game::new();
+    let client = game.connect("newcomer", false);
+
+    let names = |registry: &TileRegistry| -> Vec<String> {
+        registry.iter().map(|(_, def)| def.name.clone()).collect()
+    };
+    let server_names = names(game.server.world.resource());
+    assert!(!server_names.is_empty());
+    assert_eq!(names(game.clients[client].world.resource()), server_names);
+}
+
+#[test]
+fn only_tiles_that_drop_an_item_are_placeable() {
+    let mut game = Game::new();
+    let client = game.connect("builder", false);
+
+    let registry = game.clients[client].world.resource::<TileRegistry>();
+    let placeable: Vec<&str> = registry
+        .placeable()
+        .map(|(_, def)| def.name.as_str())
+        .collect();
+    assert!(placeable.contains(&"stone"));
+    assert!(!placeable.contains(&"lava"));
+    assert!(!placeable.contains(&"spawn"));
+}
+
+#[test]
+fn players_spawn_at_spawn_tiles() {
+    let mut game = Game::new();
+    let player = game.connect("newcomer", false);
+
+    let spawn = game.kind("spawn");
+    let pos = game.player_pos(player);
+    assert!(game
+        .server_tiles()
+        .iter()
+        .any(|(&tile, &(_, kind))| kind == spawn && tile.as_vec2() * TILE_SIZE == pos));
+}
+
+#[test]
+fn positions_are_saved_while_playing() {
+    let dir = test_dir("positions_are_saved_while_playing");
+    let settings = ServerSettings {
+        save_dir: Some(dir.clone()),
+        ..settings()
+    };
+    let mut game = Game::build(settings, None, None);
+    let player = game.connect("wanderer", false);
+
+    // Longer than the save interval, without ever leaving.
+    game.step(31 * 60);
+
+    let save = server::PlayerSave::load(&dir, "wanderer").expect("The player was saved");
+    assert_eq!(save.position, Some(game.player_pos(player)));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn similar_names_get_separate_saves() {
+    let dir = test_dir("similar_names_get_separate_saves");
+    let names = ["a.b", "a b", "a_b", "a%2Eb"];
+    for (count, name) in names.into_iter().enumerate() {
+        let mut save = server::PlayerSave::default();
+        save.inventory.add("grass", count as u32 + 1);
+        save.store(&dir, name);
+    }
+
+    for (count, name) in names.into_iter().enumerate() {
+        let save = server::PlayerSave::load(&dir, name).expect("The player was saved");
+        assert_eq!(save.inventory.count("grass"), count as u32 + 1);
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn players_get_distinct_names() {
+    let mut game = Game::new();
+    game.connect("twin", false);
+    game.connect("twin", false);
+
+    let lobby = game.server.world.resource::<server::Lobby>();
+    let first = &lobby.players[&game.client_id(0)].name;
+    let second = &lobby.players[&game.client_id(1)].name;
+    assert_eq!(first, "twin");
+    assert_ne!(first, second);
+}
+
+#[test]
+fn mined_blocks_break_everywhere_and_drop_an_item() {
+    let mut game = Game::new();
+    game.connect("miner", false);
+    game.connect("watcher", false);
+
+    let (pos, id) = game.tile_in_reach(0, "grass");
+    game.mine(0, id);
+
+    assert!(!game.server_tiles().contains_key(&pos));
+    assert!(!game.client_tiles(0).contains_key(&pos));
+    assert!(!game.client_tiles(1).contains_key(&pos));
+    assert_eq!(
+        game.clients[0].world.resource::<Inventory>().count("grass"),
+        1
+    );
+    assert_eq!(
+        game.clients[1].world.resource::<Inventory>().count("grass"),
+        0
+    );
+}
+
+#[test]
+fn impossible_mining_damage_is_ignored() {
+    let mut game = Game::new();
+    game.connect("cheater", false);
+
+    let (pos, id) = game.tile_in_reach(0, "grass");
+    game.clients[0]
+        .world
+        .resource_mut::<NetClient>()
+        .send(ClientUnreliable::MineBlock(id, f32::NAN));
+    game.step(ROUND_TRIP_FRAMES);
+    assert!(game.server_tiles().contains_key(&pos));
+
+    game.mine(0, id);
+    assert!(!game.server_tiles().contains_key(&pos));
+}
+
+#[test]
+fn malformed_messages_are_dropped() {
+    let mut game = Game::new();
+    game.connect("fuzzer", false);
+
+    let mut client = game.clients[0].world.resource_mut::<NetClient>();
+    for channel in [ClientReliable::CHANNEL_ID, ClientUnreliable::CHANNEL_ID] {
+        client.send_message(channel, vec![0xff; 7]);
+    }
+    game.step(ROUND_TRIP_FRAMES);
+
+    // The server keeps serving the client after ignoring its garbage.
+    let (pos, id) = game.tile_in_reach(0, "grass");
+    game.mine(0, id);
+    assert!(!game.client_tiles(0).contains_key(&pos));
+}
+
+#[test]
+fn placed_blocks_appear_everywhere() {
+    let mut game = Game::new();
+    game.connect("builder", false);
+    game.connect("watcher", false);
+
+    let (pos, id) = game.tile_in_reach(0, "grass");
+    game.mine(0, id);
+    let grass = game.kind("grass");
+    game.place(0, pos, grass);
+
+    let (server_id, kind) = game.server_tiles()[&pos];
+    assert_eq!(kind, grass);
+    assert_eq!(game.client_tiles(0)[&pos], (server_id, grass));
+    assert_eq!(game.client_tiles(1)[&pos], (server_id, grass));
+    assert_eq!(
+        game.clients[0].world.resource::<Inventory>().count("grass"),
+        0
+    );
+}
+
+#[test]
+fn blocks_cannot_be_placed_without_the_item() {
+    let mut game = Game::new();
+    game.connect("pauper", false);
+
+    let (pos, id) = game.tile_in_reach(0, "grass");
+    game.mine(0, id);
+    let stone = game.kind("stone");
+    game.place(0, pos, stone);
+
+    assert!(!game.server_tiles().contains_key(&pos));
+    assert!(!game.client_tiles(0).contains_key(&pos));
+    assert_eq!(
+        game.clients[0].world.resource::<Inventory>().count("grass"),
+        1
+    );
+}
+
+#[test]
+fn spawn_points_cannot_be_built_on_or_mined() {
+    let mut game = Game::new();
+    game.connect("griefer", false);
+
+    let (_, id) = game.tile_in_reach(0, "grass");
+    game.mine(0, id);
+    let (spawn, spawn_id) = game.tile_in_reach(0, "spawn");
+    let below = spawn - IVec2::Y;
+    game.place(0, below, game.kind("grass"));
+    game.mine(0, spawn_id);
+
+    assert!(!game.server_tiles().contains_key(&below));
+    assert!(game.server_tiles().contains_key(&spawn));
+    assert_eq!(
+        game.clients[0].world.resource::<Inventory>().count("grass"),
+        1
+    );
+}
+
+#[test]
+fn disconnected_players_leave_every_lobby() {
+    let mut game = Game::new();
+    game.connect("leaver", false);
+    game.connect("stayer", false);
+    let leaver = game.client_id(0);
+
+    game.clients[0]
+        .world
+        .resource_mut::<NetClient>()
+        .disconnect();
+    game.step(ROUND_TRIP_FRAMES);
+
+    let server_lobby = game.server.world.resource::<server::Lobby>();
+    assert!(!server_lobby.players.contains_key(&leaver));
+    let lobby = game.clients[1].world.resource::<client::Lobby>();
+    assert!(!lobby.players.contains_key(&leaver));
+    assert_eq!(lobby.players.len(), 1);
+}
+
+#[test]
+fn spectators_see_players_without_being_one() {
+    let mut game = Game::new();
+    game.connect("player", false);
+    let spectator = game.connect("watcher", true);
+
+    let server_lobby = game.server.world.resource::<server::Lobby>();
+    assert_eq!(server_lobby.players.len(), 1);
+    assert_eq!(server_lobby.spectators.len(), 1);
+    let lobby = game.clients[spectator].world.resource::<client::Lobby>();
+    assert_eq!(lobby.players.len(), 1);
+    assert_eq!(game.client_tiles(spectator).0, game.server_tiles().0);
+}
+
+#[test]
+fn hazardous_tiles_hurt_and_the_void_kills() {
+    let mut game = Game::new();
+    let victim = game.connect("victim", false);
+    let watcher = game.connect("watcher", false);
+
+    let (pos, _) = game.tile_in_reach(victim, "grass");
+    let lava = game.kind("lava");
+    game.server
+        .world
+        .resource_mut::<Tiles>()
+        .get_mut(&pos)
+        .unwrap()
+        .1 = lava;
+    game.teleport(victim, pos.as_vec2() * TILE_SIZE);
+    game.step(15);
+
+    let burnt = game.health(victim);
+    assert!(
+        burnt > 0.0 && burnt < MAX_HEALTH,
+        "{burnt} health after lava"
+    );
+    assert!(game.is_alive(victim));
+    // Health is only sent when it drops past a whole point, so others see it a little late.
+    assert!(game.seen_health(watcher, victim) < MAX_HEALTH);
+
+    let void_height = game.server.world.resource::<ServerSettings>().void_height;
+    game.teleport(victim, Vec2::new(0.0, void_height - TILE_SIZE));
+    game.step(ROUND_TRIP_FRAMES);
+
+    assert!(!game.is_alive(victim));
+    assert_eq!(game.seen_health(watcher, victim), 0.0);
+}
+
+#[test]
+fn dead_players_respawn_at_a_spawn_tile() {
+    let mut game = Game::new();
+    let victim = game.connect("victim", false);
+    let watcher = game.connect("watcher", false);
+
+    let settings = game.server.world.resource::<ServerSettings>().clone();
+    game.teleport(victim, Vec2::new(0.0, settings.void_height - TILE_SIZE));
+    game.step(ROUND_TRIP_FRAMES);
+    assert!(!game.is_alive(victim));
+
+    game.step((settings.respawn_seconds / FIXED_DT) as usize + ROUND_TRIP_FRAMES);
+
+    assert!(game.is_alive(victim));
+    assert_eq!(game.health(victim), MAX_HEALTH);
+    let spawn = game.kind("spawn");
+    let pos = game.player_pos(victim);
+    assert!(game
+        .server_tiles()
+        .iter()
+        .any(|(&tile, &(_, kind))| kind == spawn && tile.as_vec2() * TILE_SIZE == pos));
+    let id = game.client_id(victim);
+    let world = &game.clients[watcher].world;
+    let player = world.resource::<client::Lobby>().players[&id];
+    assert_eq!(
+        world.get::<Transform>(player).unwrap().translation.xy(),
+        pos
+    );
+    assert_eq!(game.seen_health(watcher, victim), MAX_HEALTH);
+}
+
+#[test]
+fn teammates_cannot_hurt_each_other_without_friendly_fire() {
+    let mut game = Game::new();
+    let attacker = game.connect("attacker", false);
+    let enemy = game.connect("enemy", false);
+    let teammate = game.connect("teammate", false);
+    assert_eq!(game.team(attacker), game.team(teammate));
+    assert_ne!(game.team(attacker), game.team(enemy));
+
+    let pos = game.player_pos(attacker);
+    game.teleport(enemy, pos);
+    game.teleport(teammate, pos);
+    for target in [teammate, enemy] {
+        let target = game.client_id(target);
+        game.clients[attacker]
+            .world
+            .resource_mut::<NetClient>()
+            .send(ClientReliable::Attack(target));
+        game.step(ROUND_TRIP_FRAMES);
+    }
+
+    assert_eq!(game.health(teammate), MAX_HEALTH);
+    assert!(game.health(enemy) < MAX_HEALTH);
+}
+
+#[test]
+fn team_changes_that_unbalance_teams_are_refused() {
+    let mut game = Game::new();
+    let first = game.connect("first", false);
+    let second = game.connect("second", false);
+    let third = game.connect("third", false);
+    let (red, blue) = (TeamId(0), TeamId(1));
+    assert_eq!(
+        [game.team(first), game.team(second), game.team(third)],
+        [Some(red), Some(blue), Some(red)]
+    );
+
+    let change_team = |game: &mut Game, client: usize, team: TeamId| {
+        game.clients[client]
+            .world
+            .resource_mut::<NetClient>()
+            .send(ClientReliable::ChangeTeam(team));
+        game.step(ROUND_TRIP_FRAMES);
+    };
+    // Red has two players to blue's one, so one of them may even it out.
+    change_team(&mut game, third, blue);
+    assert_eq!(game.team(third), Some(blue));
+    // Now blue has two, and moving there would leave red alone and outnumbered.
+    change_team(&mut game, first, blue);
+    assert_eq!(game.team(first), Some(red));
+
+    let id = game.client_id(third);
+    let world = &game.clients[second].world;
+    let player = world.resource::<client::Lobby>().players[&id];
+    assert_eq!(world.get::<PlayerTeam>(player).unwrap().0, Some(blue));
+}
+
+#[test]
+fn reliable_messages_survive_a_lossy_connection() {
+    let mut game = Game::with_conditions(LinkConditions {
+        latency: Duration::from_millis(50),
+        loss: 0.5,
+        ..default()
+    });
+    game.connect("alice", false);
+    game.connect("bob", false);
+
+    let (pos, id) = game.tile_in_reach(0, "grass");
+    // Mining is unreliable, so it is simply retried like holding the button would.
+    for _ in 0..50 {
+        if !game.server_tiles().contains_key(&pos) {
+            break;
+        }
+        game.mine(0, id);
+    }
+    game.step(MAX_RESEND_FRAMES);
+    let grass = game.kind("grass");
+    game.place(0, pos, grass);
+    // Each way takes three frames, and lost messages are resent a round trip later.
+    assert!(!game.client_tiles(1).contains_key(&pos));
+    game.step(MAX_RESEND_FRAMES);
+
+    assert_eq!(game.server_tiles()[&pos].1, grass);
+
+    for client in 0..2 {
+        assert_eq!(
+            game.clients[client]
+                .world
+                .resource::<client::Lobby>()
+                .players
+                .len(),
+            2
+        );
+        assert_eq!(game.client_tiles(client).0, game.server_tiles().0);
+    }
+}
+
+#[test]
+fn lost_reliable_messages_arrive_late_but_in_order() {
+    let latency = Duration::from_millis(50);
+    let (mut server, mut proxy) = udp_server(LinkConditions {
+        latency,
+        loss: 0.5,
+        ..default()
+    });
+    let mut client = udp_client(proxy.addr(), 1, &ConnectInfo::default());
+    let frame = Duration::from_millis(10);
+    let mut now = Duration::ZERO;
+    let mut step = |server: &mut RenetServer, client: &mut RenetClient| {
+        now += frame;
+        proxy.update(frame).unwrap();
+        ServerTransport::update(server, frame).unwrap();
+        ServerTransport::send_packets(server).unwrap();
+        ClientTransport::update(client, frame).unwrap();
+        ClientTransport::send_packets(client).unwrap();
+        now
+    };
+    while !client.is_connected() || server.clients_id().is_empty() {
+        assert!(step(&mut server, &mut client) < Duration::from_secs(5));
+    }
+
+    // One message a frame, so each goes out in packets of its own.
+    let channel = ServerReliable::CHANNEL_ID;
+    let mut sent_at = Vec::new();
+    let mut received = Vec::new();
+    let mut delays = Vec::new();
+    for i in 0..300 {
+        if i < 100 {
+            server.send_message(1, channel, vec![i as u8]);
+        }
+        let now = step(&mut server, &mut client);
+        if i < 100 {
+            sent_at.push(now);
+        }
+        while let Some(message) = client.receive_message(channel) {
+            delays.push(now - sent_at[message[0] as usize]);
+            received.extend(message);
+        }
+    }
+
+    assert_eq!(received, (0..100).collect::<Vec<u8>>());
+    assert!(delays.iter().all(|&delay| delay >= latency));
+    // Renet resent the lost ones, at least a round trip later.
+    assert!(
+        delays.iter().any(|&delay| delay >= latency * 3),
+        "All arrived within {:?}",
+        delays.iter().max()
+    );
+}
+
+#[test]
+fn traffic_is_counted_per_message_type() {
+    let mut game = Game::new();
+    game.connect("miner", false);
+    let (_, id) = game.tile_in_reach(0, "grass");
+    game.mine(0, id);
+
+    let stats = game.stats.get(game.client_id(0)).unwrap();
+    let sent: Vec<_> = stats
+        .sent
+        .per_message()
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect();
+    let received: Vec<_> = stats
+        .received
+        .per_message()
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect();
+    for name in ["TransferChunk", "SyncRules", "InventoryChanged"] {
+        assert!(sent.contains(&name), "{name} missing from {sent:?}");
+    }
+    assert!(
+        received.contains(&"MineBlock"),
+        "MineBlock missing from {received:?}"
+    );
+}
+
+#[test]
+fn replays_record_both_directions_in_order() {
+    let dir = test_dir("replays_record_both_directions_in_order");
+    let mut game = Game::recording(&dir);
+    game.connect("miner", false);
+    let (_, id) = game.tile_in_reach(0, "grass");
+    game.mine(0, id);
+    drop(game);
+
+    let replay = std::fs::read_dir(&dir)
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap()
+        .path();
+    let entries = load_replay(&replay);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(entries.windows(2).all(|pair| pair[0].tick <= pair[1].tick));
+    let connected = entries
+        .iter()
+        .position(|entry| matches!(entry.event, ReplayEvent::Connected(1, _)))
+        .expect("The connection is recorded");
+    let mined = entries
+        .iter()
+        .position(|entry| {
+            matches!(&entry.event, ReplayEvent::Received { client_id: 1, channel_id, message }
+                if message_name(false, *channel_id, message) == "MineBlock")
+        })
+        .expect("Mining is recorded");
+    let broken = entries
+        .iter()
+        .position(|entry| {
+            matches!(&entry.event, ReplayEvent::Sent { channel_id, message, .. }
+                if message_name(true, *channel_id, message) == "Event")
+        })
+        .expect("The broken block is recorded");
+    assert!(connected < mined && mined < broken);
+}
+
+#[test]
+fn replayed_inputs_rebuild_the_same_world() {
+    let dir = test_dir("replayed_inputs_rebuild_the_same_world");
+    let mut game = Game::recording(&dir);
+    game.connect("builder", false);
+    game.connect("miner", false);
+    let (pos, id) = game.tile_in_reach(0, "grass");
+    game.mine(0, id);
+    let grass = game.kind("grass");
+    game.place(0, pos, grass);
+    let (_, id) = game.tile_in_reach(1, "stone");
+    game.mine(1, id);
+    game.clients[1]
+        .world
+        .resource_mut::<NetClient>()
+        .disconnect();
+    game.step(ROUND_TRIP_FRAMES);
+    let frames = game.server.world.resource::<ServerTick>().0;
+    let expected = world_hash(&game.server.world);
+    drop(game);
+
+    let replay = std::fs::read_dir(&dir)
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap()
+        .path();
+    let entries = load_replay(&replay);
+    std::fs::remove_dir_all(&dir).unwrap();
+    let mut server = server_app(NetServer::new(Playback::new(entries)), settings());
+    for _ in 0..frames {
+        server.update();
+    }
+
+    assert_eq!(world_hash(&server.world), expected);
+}
+
+#[test]
+fn joining_clients_get_the_servers_textures() {
+    let mut game = Game::new();
+    let texture = TileTexture {
+        path: "tiles/custom.png".to_string(),
+        bytes: vec![1, 2, 3],
+    };
+    game.server
+        .world
+        .insert_resource(server::TileTextures(vec![texture.clone()]));
+    let client = game.connect("newcomer", false);
+
+    let textures = &game.clients[client]
+        .world
+        .resource::<client::ServerTextures>()
+        .0;
+    assert_eq!(textures.len(), 1);
+    assert_eq!(textures[0].path, texture.path);
+    assert_eq!(textures[0].bytes, texture.bytes);
+}
+
+#[test]
+fn players_can_download_their_save() {
+    let mut game = Game::new();
+    game.connect("hoarder", false);
+    let (_, id) = game.tile_in_reach(0, "grass");
+    game.mine(0, id);
+
+    game.clients[0]
+        .world
+        .resource_mut::<NetClient>()
+        .send(ClientReliable::DownloadSave);
+    let mut downloaded = ManualEventReader::<client::DownloadedSave>::default();
+    let mut save = None;
+    for _ in 0..MAX_SYNC_FRAMES {
+        game.step(1);
+        let events = game.clients[0]
+            .world
+            .resource::<Events<client::DownloadedSave>>();
+        if let Some(event) = downloaded.iter(events).next() {
+            save = Some(event.0.clone());
+            break;
+        }
+    }
+
+    let save: server::PlayerSave = ron::de::from_bytes(&save.expect("The save arrived")).unwrap();
+    assert_eq!(save.inventory.count("grass"), 1);
+}
+
+#[test]
+fn large_worlds_arrive_in_chunks_over_a_lossy_connection() {
+    let mut game = Game::with_conditions(LinkConditions {
+        loss: 0.3,
+        ..default()
+    });
+    let stone = game.kind("stone");
+    for x in 10..400 {
+        for y in -10..0 {
+            let tile = NetworkId(game.server.world.spawn().id());
+            game.server
+                .world
+                .resource_mut::<Tiles>()
+                .insert(IVec2::new(x, y), (tile, stone));
+        }
+    }
+    let client = game.connect("downloader", false);
+
+    let world = WorldData {
+        registry: game.server.world.resource::<TileRegistry>().clone(),
+        tiles: game.server_tiles().clone(),
+    };
+    assert!(world.pack(Some(Compression::Zstd)).len() > 10 * CHUNK_SIZE);
+    assert!(
+        game.clients[client]
+            .world
+            .resource::<client::WorldSync>()
+            .loaded
+    );
+    assert_eq!(game.client_tiles(client).0, game.server_tiles().0);
+}
+
+#[test]
+fn oversized_transfers_are_refused() {
+    let mut downloads = Downloads::default();
+    let chunk = Chunk {
+        transfer: 7,
+        kind: TransferKind::World,
+        size: TransferKind::World.max_size() as u32 + 1,
+        index: 0,
+        bytes: vec![0; CHUNK_SIZE],
+    };
+    assert!(matches!(
+        downloads.receive(chunk.clone()),
+        Received::Refused(7)
+    ));
+    assert!(matches!(downloads.receive(chunk), Received::Pending));
+    assert!(downloads.progress().is_empty());
+    assert!(downloads.take_acks().is_empty());
+}
+
+#[test]
+fn compression_is_negotiated_per_client() {
+    let transport = MemoryServer::new();
+    let mut server = Compressed::new(transport.clone(), vec![Compression::Zstd, Compression::Lz4]);
+    let offer = |compression: Vec<Compression>| {
+        ConnectInfo {
+            name: "client".to_string(),
+            spectator: false,
+            compression,
+        }
+        .encode()
+    };
+    // Bare clients see what goes over the wire.
+    let mut lz4 = transport.connect(1, offer(vec![Compression::Lz4]));
+    let mut plain = transport.connect(2, offer(Vec::new()));
+    let mut both = Compressed::new(
+        transport.connect(3, offer(Compression::ALL.to_vec())),
+        Compression::ALL.to_vec(),
+    );
+    while server.get_event().is_some() {}
+
+    let message = bincode::serialize(&vec![IVec2::new(4, 2); 500]).unwrap();
+    server.broadcast_message(0, message.clone());
+    lz4.receive_message(0)
+        .expect("The server says what it picked");
+    let packed = lz4.receive_message(0).unwrap();
+    assert_eq!(packed[0], Compression::Lz4.flag());
+    assert!(packed.len() < message.len() / 10);
+    assert_eq!(plain.receive_message(0).unwrap()[1..], message[..]);
+    assert_eq!(both.receive_message(0), Some(message.clone()));
+
+    both.send_message(0, message.clone());
+    assert_eq!(server.receive_message(3, 0), Some(message));
+}
+
+#[test]
+fn transfer_chunks_are_not_compressed_again() {
+    let transport = MemoryServer::new();
+    let mut server = Compressed::new(transport.clone(), Compression::ALL.to_vec());
+    let info = ConnectInfo {
+        name: "downloader".to_string(),
+        spectator: false,
+        compression: Compression::ALL.to_vec(),
+    };
+    let mut client = transport.connect(1, info.encode());
+    while server.get_event().is_some() {}
+    client
+        .receive_message(ServerReliable::CHANNEL_ID)
+        .expect("The server says what it picked");
+
+    let chunk = ServerUnreliable::TransferChunk(Chunk {
+        transfer: 1,
+        kind: TransferKind::World,
+        size: CHUNK_SIZE as u32,
+        index: 0,
+        bytes: vec![0; CHUNK_SIZE],
+    })
+    .prepare();
+    server.send_message(1, ServerUnreliable::CHANNEL_ID, chunk.clone());
+    let packed = client
+        .receive_message(ServerUnreliable::CHANNEL_ID)
+        .unwrap();
+    assert_eq!(packed, pack(None, &chunk));
+}
+
+#[test]
+fn compression_bombs_are_dropped() {
+    let transport = MemoryServer::new();
+    let mut server = Compressed::new(transport.clone(), Compression::ALL.to_vec());
+    let info = ConnectInfo {
+        name: "bomber".to_string(),
+        ..default()
+    };
+    let mut client = transport.connect(1, info.encode());
+    while server.get_event().is_some() {}
+
+    let huge = vec![0; 64 * 1024 * 1024];
+    client.send_message(0, pack(Some(Compression::Zstd), &huge));
+    client.send_message(0, pack(Some(Compression::Lz4), &huge));
+    client.send_message(
+        0,
+        vec![
+            Compression::Lz4.flag(),
+            0,
+            0,
+            0,
+            0,
+            0xff,
+            0xff,
+            0xff,
+            0x7f,
+            0,
+        ],
+    );
+    client.send_message(0, pack(None, b"fine"));
+    assert_eq!(server.receive_message(1, 0), Some(b"fine".to_vec()));
+}
+
+#[test]
+fn traffic_is_counted_compressed() {
+    let transport = MemoryServer::new();
+    let stats = NetworkStats::default();
+    let mut server = Compressed::new(
+        Metered::new(transport.clone(), stats.clone()),
+        vec![Compression::Zstd],
+    );
+    let info = ConnectInfo {
+        name: "client".to_string(),
+        compression: vec![Compression::Zstd],
+        ..default()
+    };
+    let mut client = transport.connect(1, info.encode());
+    while server.get_event().is_some() {}
+
+    // An `InventoryChanged` that compresses well.
+    let message = [&4u32.to_le_bytes()[..], &[7; 4096]].concat();
+    server.send_message(1, ServerReliable::CHANNEL_ID, message);
+    let _chosen = client.receive_message(ServerReliable::CHANNEL_ID).unwrap();
+    let packed = client.receive_message(ServerReliable::CHANNEL_ID).unwrap();
+
+    let totals = stats.totals();
+    let sent = totals.sent[&(ServerReliable::CHANNEL_ID, "InventoryChanged")];
+    assert_eq!(sent.bytes, packed.len() as u64);
+    assert!(packed.len() < 4096);
+    assert!(totals
+        .sent
+        .contains_key(&(ServerReliable::CHANNEL_ID, "CompressionChosen")));
+}
+
+#[test]
+fn movement_batches_pack_positions_into_a_few_bits() {
+    let batch = MovementBatch(vec![
+        (PlayerIndex(0), PlayerLocation(Vec2::new(100.3, 400.0))),
+        (PlayerIndex(5), PlayerLocation(Vec2::new(-2000.0, 0.01))),
+        (
+            PlayerIndex(3),
+            PlayerLocation(Vec2::new(123456.7, -98765.4)),
+        ),
+    ]);
+    let bytes = bincode::serialize(&batch).unwrap();
+    // Index width and bincode's length prefix, then three 3 bit indices with two 24 bit axes
+    // each.
+    assert_eq!(bytes.len(), 1 + 8 + (3 * (3 + 48usize)).div_ceil(8));
+
+    let MovementBatch(moves) = bincode::deserialize(&bytes).unwrap();
+    for ((index, PlayerLocation(pos)), (sent_index, PlayerLocation(sent))) in
+        moves.iter().zip(&batch.0)
+    {
+        assert_eq!(index, sent_index);
+        assert!(
+            pos.distance(*sent) <= POSITION_STEP,
+            "{pos} is too far from {sent}"
+        );
+    }
+    assert!(bincode::deserialize::<MovementBatch>(&bytes[..bytes.len() - 1]).is_err());
+    // Four 51 bit moves fill 26 bytes and five fill 32, so 28 bytes can't be whole moves.
+    let padded = bincode::serialize(&(3u8, vec![0u8; 28])).unwrap();
+    assert!(bincode::deserialize::<MovementBatch>(&padded).is_err());
+}
+
+#[test]
+fn moving_players_reach_every_other_client() {
+    let mut game = Game::new();
+    let runner = game.connect("runner", false);
+    let watcher = game.connect("watcher", false);
+    let target = game.player_pos(runner) + Vec2::new(30.0, 12.3);
+    game.clients[runner]
+        .world
+        .resource_mut::<NetClient>()
+        .send(ClientUnreliable::PlayerMovement(PlayerLocation(target)));
+    game.step(ROUND_TRIP_FRAMES);
+
+    let runner_id = game.client_id(runner);
+    assert!(game.player_pos(runner).distance(target) <= POSITION_STEP);
+    let seen = game.clients[watcher]
+        .world
+        .resource::<client::Lobby>()
+        .players[&runner_id];
+    let seen = game.clients[watcher]
+        .world
+        .get::<Transform>(seen)
+        .unwrap()
+        .translation
+        .truncate();
+    assert!(
+        seen.distance(target) <= POSITION_STEP,
+        "{seen} is too far from {target}"
+    );
+}
+
+#[test]
+fn movers_are_not_sent_their_own_moves() {
+    let dir = test_dir("movers_are_not_sent_their_own_moves");
+    let mut game = Game::recording(&dir);
+    let runner = game.connect("runner", false);
+    let watcher = game.connect("watcher", false);
+    let target = game.player_pos(runner) + Vec2::new(30.0, 12.3);
+    game.clients[runner]
+        .world
+        .resource_mut::<NetClient>()
+        .send(ClientUnreliable::PlayerMovement(PlayerLocation(target)));
+    game.step(ROUND_TRIP_FRAMES);
+    let (runner_id, watcher_id) = (game.client_id(runner), game.client_id(watcher));
+    let runner_index = game.server.world.resource::<server::Lobby>().players[&runner_id].index;
+    drop(game);
+
+    let replay = std::fs::read_dir(&dir)
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap()
+        .path();
+    let entries = load_replay(&replay);
+    std::fs::remove_dir_all(&dir).unwrap();
+    let mut watched = false;
+    for entry in entries {
+        let (to, message) = match entry.event {
+            ReplayEvent::Sent {
+                to,
+                channel_id: ServerUnreliable::CHANNEL_ID,
+                message,
+            } => (to, message),
+            _ => continue,
+        };
+        let moves = match bincode::deserialize(&message).unwrap() {
+            ServerUnreliable::PlayersMoved(MovementBatch(moves)) => moves,
+            _ => continue,
+        };
+        let moved_runner = moves.iter().any(|&(index, _)| index == runner_index);
+        match to {
+            Recipient::One(id) if id == runner_id => assert!(!moved_runner),
+            Recipient::One(id) if id == watcher_id => watched |= moved_runner,
+            to => panic!("Movement sent to {to:?}"),
+        }
+    }
+    assert!(watched, "The watcher never saw the runner move");
+}
+
+#[test]
+fn flying_players_are_held_to_their_speed() {
+    let mut game = Game::new();
+    let runner = game.connect("teleporter", false);
+    let start = game.player_pos(runner);
+    game.clients[runner]
+        .world
+        .resource_mut::<NetClient>()
+        .send(ClientUnreliable::PlayerMovement(PlayerLocation(
+            start + Vec2::new(100_000.0, 0.0),
+        )));
+    game.step(ROUND_TRIP_FRAMES);
+
+    let pos = game.player_pos(runner);
+    assert!(
+        pos.x > start.x && pos.x < start.x + 500.0,
+        "{pos} is too far"
+    );
+    let runner_id = game.client_id(runner);
+    let player = game.clients[runner]
+        .world
+        .resource::<client::Lobby>()
+        .players[&runner_id];
+    let corrected = game.clients[runner]
+        .world
+        .get::<Transform>(player)
+        .unwrap()
+        .translation
+        .truncate();
+    assert!(corrected.distance(pos) <= POSITION_STEP);
+}
+
+#[test]
+fn platformer_inputs_are_limited_to_one_per_tick() {
+    let mut game = Game::platformer();
+    let runner = game.connect("speeder", false);
+    let runner_id = game.client_id(runner);
+    let start = game.player_pos(runner);
+
+    // Far more inputs than ticks pass, each of them running right.
+    let mut tick = game
+        .server
+        .world
+        .resource::<server::Lobby>()
+        .platformer_tick(runner_id)
+        .unwrap();
+    let run = MoveInput {
+        right: true,
+        ..default()
+    };
+    for _ in 0..20 {
+        tick += 8;
+        game.clients[runner].world.resource_mut::<NetClient>().send(
+            ClientUnreliable::PlatformerInput {
+                tick,
+                inputs: vec![run; 8],
+            },
+        );
+    }
+    game.step(ROUND_TRIP_FRAMES);
+
+    // Running for all 160 inputs would have gone about a thousand pixels.
+    let pos = game.player_pos(runner);
+    assert!(
+        pos.x > start.x && pos.x < start.x + 100.0,
+        "{pos} is too far"
+    );
+}
+
+#[test]
+fn platformer_inputs_from_the_future_are_ignored() {
+    let mut game = Game::platformer();
+    let runner = game.connect("time traveller", false);
+    let runner_id = game.client_id(runner);
+    let tick = |game: &Game| {
+        game.server
+            .world
+            .resource::<server::Lobby>()
+            .platformer_tick(runner_id)
+            .unwrap()
+    };
+    let before = tick(&game);
+
+    game.clients[runner].world.resource_mut::<NetClient>().send(
+        ClientUnreliable::PlatformerInput {
+            tick: u32::MAX - 1,
+            inputs: vec![MoveInput::default()],
+        },
+    );
+    game.step(ROUND_TRIP_FRAMES);
+
+    assert!(tick(&game) < before + 60);
+}
+
+#[test]
+fn silent_platformers_still_fall() {
+    let mut game = Game::platformer();
+    let faller = game.connect("faller", false);
+    let start = game.player_pos(faller);
+
+    game.step(60);
+
+    assert!(game.player_pos(faller).y < start.y);
+}
+
+/// Prints how well worlds compress whole, as they are sent, and for comparison chunk by chunk,
+/// and how long packing a whole world takes. Run it with
+/// `cargo test --release world_compression -- --ignored --nocapture`.
+#[test]
+#[ignore]
+fn world_compression() {
+    use std::time::Instant;
+
+    use noise::{NoiseFn, Perlin};
+
+    let registry = TileRegistry::load();
+    let kind = |name| registry.kind(name).unwrap();
+    let (stone, grass, brick) = (kind("stone"), kind("grass"), kind("brick"));
+    let mut next_id = 0;
+    let mut world = |tiles: &mut dyn Iterator<Item = (IVec2, TileKind)>| {
+        tiles
+            .map(|(pos, kind)| {
+                next_id += 1;
+                (pos, (NetworkId(Entity::from_raw(next_id)), kind))
+            })
+            .collect::<std::collections::HashMap<_, _>>()
+    };
+
+    let spawn = world(&mut (0..5).flat_map(|y| {
+        (0..5).map(move |x| {
+            let kind = if (x + y) % 2 == 0 { stone } else { grass };
+            (IVec2::new(x, y), kind)
+        })
+    }));
+    let strip =
+        world(&mut (10..200).flat_map(|x| (-10..0).map(move |y| (IVec2::new(x, y), stone))));
+    // Rolling hills of stone under grass, with caves and the odd brick build.
+    let perlin = Perlin::new(SEED as u32);
+    let terrain = world(&mut (-256..256).flat_map(|x| {
+        let height = (perlin.get([x as f64 / 40.0, 0.5]) * 16.0) as i32;
+        (-64..=height).filter_map(move |y| {
+            let cave = perlin.get([x as f64 / 12.0, y as f64 / 12.0]) > 0.35;
+            let kind = match y {
+                _ if cave => return None,
+                _ if y == height => grass,
+                _ if (x * 7 + y * 3) % 97 == 0 => brick,
+                _ => stone,
+            };
+            Some((IVec2::new(x, y), kind))
+        })
+    }));
+
+    println!(
+        "{:<8} {:>6} {:>9} {:>5} {:>16} {:>16} {:>10}",
+        "world", "tiles", "raw", "algo", "whole", "chunked", "packing"
+    );
+    for (name, tiles) in [("spawn", spawn), ("strip", strip), ("terrain", terrain)] {
+        let data = bincode::serialize(&tiles).unwrap();
+        let chunks: Vec<_> = data
+            .chunks(CHUNK_SIZE)
+            .enumerate()
+            .map(|(index, bytes)| {
+                bincode::serialize(&ServerUnreliable::TransferChunk(Chunk {
+                    transfer: 0,
+                    kind: TransferKind::World,
+                    size: data.len() as u32,
+                    index: index as u32,
+                    bytes: bytes.to_vec(),
+                }))
+                .unwrap()
+            })
+            .collect();
+        let chunks_raw: usize = chunks.iter().map(|chunk| chunk.len()).sum();
+        for compression in Compression::ALL {
+            let start = Instant::now();
+            let whole = pack(Some(compression), &data).len();
+            let elapsed = start.elapsed();
+            let chunked: usize = chunks
+                .iter()
+                .map(|chunk| pack(Some(compression), chunk).len())
+                .sum();
+            println!(
+                "{name:<8} {:>6} {:>9} {:>5} {whole:>9} ({:>4.1}x) {chunked:>9} ({:>4.1}x) {:>8.2}ms",
+                tiles.len(),
+                data.len(),
+                format!("{compression:?}"),
+                data.len() as f32 / whole as f32,
+                chunks_raw as f32 / chunked as f32,
+                elapsed.as_secs_f64() * 1000.0,
+            );
+        }
+    }
+}
+
+/// Prints how many bytes of movement updates the server sends per player per second, as they go
+/// over the wire, with every player moving on every tick. Run it with
+/// `cargo test --release movement_bandwidth -- --ignored --nocapture`.
+#[test]
+#[ignore]
+fn movement_bandwidth() {
+    const SECONDS: usize = 2;
+
+    // Everything on the unreliable channel but world chunks.
+    let movement = |game: &Game| {
+        let mut traffic = (0, 0);
+        for (&(channel_id, name), sent) in &game.stats.totals().sent {
+            if channel_id == ServerUnreliable::CHANNEL_ID && name != "TransferChunk" {
+                traffic.0 += sent.bytes;
+                traffic.1 += sent.messages;
+            }
+        }
+        traffic
+    };
+    for players in [2, 8, 32] {
+        let mut game = Game::new();
+        for i in 0..players {
+            game.connect(&format!("runner-{i}"), false);
+        }
+        let (start_bytes, start_messages) = movement(&game);
+        for frame in 0..SECONDS * 60 {
+            for client in 0..players {
+                let pos = game.player_pos(client)
+                    + Vec2::new(1.5, if frame % 2 == 0 { 0.7 } else { -0.7 });
+                game.clients[client]
+                    .world
+                    .resource_mut::<NetClient>()
+                    .send(ClientUnreliable::PlayerMovement(PlayerLocation(pos)));
+            }
+            game.step(1);
+        }
+        let (bytes, messages) = movement(&game);
+
+        let per_player_second = |count: u64| count as f32 / (players * SECONDS) as f32;
+        println!(
+            "{players:>2} players: {:>7.0} bytes in {:>5.0} messages per player per second",
+            per_player_second(bytes - start_bytes),
+            per_player_second(messages - start_messages),
+        );
+    }
+}